@@ -1,60 +1,199 @@
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::env;
 use std::fs::File;
 use std::io::{self, BufRead};
 
+// Each rucksack is a bitmask with bit p set iff an item of priority p is present
+type ItemSet = u64;
+
+fn to_item_set(priorities: &[usize]) -> ItemSet {
+    priorities
+        .iter()
+        .fold(0, |set, &priority| set | (1 << priority))
+}
+
+// The unique priority in the set, panicking if it is empty or ambiguous
+fn single_priority(set: ItemSet, what: &str) -> usize {
+    match set.count_ones() {
+        0 => panic!("{} have no shared item; run `day_3 audit` for details", what),
+        1 => set.trailing_zeros() as usize,
+        _ => panic!(
+            "{} have several shared items ({}); run `day_3 audit` for details",
+            what,
+            describe_items(set)
+        ),
+    }
+}
+
 fn split_in_half(mut priorities: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
     let split_point: usize = priorities.len() / 2;
     let second_half = priorities.split_off(split_point);
     (priorities, second_half)
 }
 
+const ITEM_LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn priority_of(letter: char) -> Option<usize> {
+    ITEM_LETTERS
+        .chars()
+        .position(|elem| elem == letter)
+        .map(|idx| idx + 1)
+}
+
+fn letter_of(priority: usize) -> char {
+    ITEM_LETTERS.chars().nth(priority - 1).unwrap()
+}
+
 fn parse_line(string: String) -> Vec<usize> {
     string
         .chars()
-        .map(|letter| {
-            "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
-                .chars()
-                .position(|elem| elem == letter)
-                .expect("Weird letter")
-                + 1
-        })
+        .map(|letter| priority_of(letter).expect("Weird letter"))
         .collect()
 }
 
-fn read_lines() -> impl Iterator<Item = Vec<usize>> {
+fn read_raw_lines() -> impl Iterator<Item = String> {
     let file = File::open("./data/input").expect("Input file not found");
     let lines = io::BufReader::new(file).lines();
-    lines
-        .map(|line| line.expect("Couldn't read line of input"))
-        .map(parse_line)
+    lines.map(|line| line.expect("Couldn't read line of input"))
+}
+
+fn read_lines() -> impl Iterator<Item = Vec<usize>> {
+    read_raw_lines().map(parse_line)
 }
 
 fn find_common_priority(pair: (Vec<usize>, Vec<usize>)) -> usize {
-    let set0: HashSet<usize> = HashSet::from_iter(pair.0.into_iter());
-    let set1: HashSet<usize> = HashSet::from_iter(pair.1.into_iter());
-    let mut intersection = set0.intersection(&set1).map(|&elem| elem);
-    intersection.next().expect("Ruckscaks have no intersection")
+    let intersection = to_item_set(&pair.0) & to_item_set(&pair.1);
+    single_priority(intersection, "Rucksack halves")
 }
 
 fn find_badge(chunk: Vec<Vec<usize>>) -> usize {
-    let set0: HashSet<usize> = HashSet::from_iter(chunk[0].clone().into_iter());
-    let set1: HashSet<usize> = HashSet::from_iter(chunk[1].clone().into_iter());
-    let set2: HashSet<usize> = HashSet::from_iter(chunk[2].clone().into_iter());
-    let intersection1: HashSet<usize> =
-        HashSet::from_iter(set0.intersection(&set1).map(|&elem| elem));
-    let mut intersection2 = intersection1.intersection(&set2).map(|&elem| elem);
-    intersection2.next().expect("No badge")
+    let intersection = chunk
+        .iter()
+        .map(|rucksack| to_item_set(rucksack))
+        .fold(ItemSet::MAX, |acc, set| acc & set);
+    single_priority(intersection, "Group rucksacks")
+}
+
+fn describe_items(set: ItemSet) -> String {
+    (1..=52)
+        .filter(|priority| set & (1 << priority) != 0)
+        .map(letter_of)
+        .join(", ")
+}
+
+// Valid items in a line, ignoring anything that isn't a letter
+fn lenient_item_set(line: &str) -> ItemSet {
+    let priorities: Vec<usize> = line.chars().filter_map(priority_of).collect();
+    to_item_set(&priorities)
+}
+
+fn audit_rucksack(line: &str) -> Vec<String> {
+    let mut issues = vec![];
+    let length = line.chars().count();
+    if !length.is_multiple_of(2) {
+        issues.push(format!("odd length ({})", length));
+    }
+    let illegal: Vec<char> = line
+        .chars()
+        .filter(|&letter| priority_of(letter).is_none())
+        .unique()
+        .collect();
+    if !illegal.is_empty() {
+        issues.push(format!(
+            "illegal characters {}",
+            illegal.iter().map(|letter| format!("{:?}", letter)).join(", ")
+        ));
+    }
+    let first_half: String = line.chars().take(length / 2).collect();
+    let second_half: String = line.chars().skip(length / 2).collect();
+    let shared = lenient_item_set(&first_half) & lenient_item_set(&second_half);
+    match shared.count_ones() {
+        0 => issues.push("no shared item".to_string()),
+        1 => {}
+        _ => issues.push(format!("several shared items ({})", describe_items(shared))),
+    }
+    issues
+}
+
+fn audit_group(group: &[(usize, String)], group_size: usize) -> Vec<String> {
+    let mut issues = vec![];
+    if group.len() < group_size {
+        issues.push(format!(
+            "incomplete group ({} of {} rucksacks)",
+            group.len(),
+            group_size
+        ));
+    }
+    let badges = group
+        .iter()
+        .map(|(_, line)| lenient_item_set(line))
+        .fold(ItemSet::MAX, |acc, set| acc & set);
+    match badges.count_ones() {
+        0 => issues.push("no badge".to_string()),
+        1 => {}
+        _ => issues.push(format!("several badges ({})", describe_items(badges))),
+    }
+    issues
+}
+
+fn audit(group_size: usize) {
+    let lines: Vec<(usize, String)> = read_raw_lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .collect();
+    let mut clean = true;
+
+    for (line_no, line) in lines.iter() {
+        for issue in audit_rucksack(line) {
+            println!("line {}: {}", line_no, issue);
+            clean = false;
+        }
+    }
+
+    for (group_idx, group) in lines.chunks(group_size).enumerate() {
+        let first = group.first().unwrap().0;
+        let last = group.last().unwrap().0;
+        for issue in audit_group(group, group_size) {
+            println!(
+                "group {} (lines {}-{}): {}",
+                group_idx + 1,
+                first,
+                last,
+                issue
+            );
+            clean = false;
+        }
+    }
+
+    if clean {
+        println!("No issues found");
+    }
+}
+
+fn parse_group_size(arg: Option<String>) -> usize {
+    let group_size = arg
+        .map(|arg| arg.parse().expect("Group size should be a positive integer"))
+        .unwrap_or(3);
+    assert!(group_size > 0, "Group size should be a positive integer");
+    group_size
 }
 
 fn main() {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(|arg| arg.as_str()) == Some("audit") {
+        args.next();
+        audit(parse_group_size(args.next()));
+        return;
+    }
+    let group_size = parse_group_size(args.next());
+
     let lines = read_lines();
-    let split_lines = lines.map(|line| split_in_half(line));
-    let common = split_lines.map(|pair| find_common_priority(pair));
+    let split_lines = lines.map(split_in_half);
+    let common = split_lines.map(find_common_priority);
     println!("{}", common.sum::<usize>());
 
-    let triples = read_lines().chunks(3);
-    let triples_as_vecs = triples.into_iter().map(|chunk| chunk.collect());
-    let badges = triples_as_vecs.map(find_badge);
+    let groups = read_lines().chunks(group_size);
+    let groups_as_vecs = groups.into_iter().map(|chunk| chunk.collect());
+    let badges = groups_as_vecs.map(find_badge);
     println!("{}", badges.sum::<usize>());
 }
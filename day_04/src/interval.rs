@@ -0,0 +1,180 @@
+// Closed intervals of section IDs, inclusive at both ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ElfRange {
+    pub from: u32,
+    pub to: u32,
+}
+
+impl ElfRange {
+    pub fn len(&self) -> u64 {
+        (self.to - self.from) as u64 + 1
+    }
+
+    pub fn overlaps(&self, other: &ElfRange) -> bool {
+        self.from <= other.to && other.from <= self.to
+    }
+
+    pub fn contains(&self, other: &ElfRange) -> bool {
+        self.from <= other.from && self.to >= other.to
+    }
+
+    pub fn intersection(&self, other: &ElfRange) -> Option<ElfRange> {
+        if self.overlaps(other) {
+            Some(ElfRange {
+                from: self.from.max(other.from),
+                to: self.to.min(other.to),
+            })
+        } else {
+            None
+        }
+    }
+
+    // Only defined when the result is itself a range, i.e. the two overlap or touch
+    pub fn union(&self, other: &ElfRange) -> Option<ElfRange> {
+        let touching =
+            (self.to as u64) + 1 >= other.from as u64 && (other.to as u64) + 1 >= self.from as u64;
+        if touching {
+            Some(ElfRange {
+                from: self.from.min(other.from),
+                to: self.to.max(other.to),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+// Sorted, disjoint, non-adjacent ranges covering the same sections as the input
+pub fn merge(ranges: impl IntoIterator<Item = ElfRange>) -> Vec<ElfRange> {
+    let mut sorted: Vec<ElfRange> = ranges.into_iter().collect();
+    sorted.sort();
+    let mut merged: Vec<ElfRange> = vec![];
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) => match last.union(&range) {
+                Some(combined) => *last = combined,
+                None => merged.push(range),
+            },
+            None => merged.push(range),
+        }
+    }
+    merged
+}
+
+pub fn total_covered(ranges: &[ElfRange]) -> u64 {
+    merge(ranges.iter().copied())
+        .iter()
+        .map(ElfRange::len)
+        .sum()
+}
+
+// Maximal ranges of sections assigned to strictly more than k elves
+pub fn covered_more_than(ranges: &[ElfRange], k: usize) -> Vec<ElfRange> {
+    // Sweep over +1 at the start of each range and -1 just past its end
+    let mut events: Vec<(u64, i64)> = ranges
+        .iter()
+        .flat_map(|range| [(range.from as u64, 1), (range.to as u64 + 1, -1)])
+        .collect();
+    events.sort();
+
+    let mut segments = vec![];
+    let mut depth: i64 = 0;
+    let mut start: Option<u64> = None;
+    for (position, delta) in events {
+        depth += delta;
+        let above = depth > k as i64;
+        match (start, above) {
+            (None, true) => start = Some(position),
+            (Some(from), false) => {
+                if from < position {
+                    segments.push(ElfRange {
+                        from: from as u32,
+                        to: (position - 1) as u32,
+                    });
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    merge(segments)
+}
+
+// Indices of ranges whose every section is also assigned to some other elf
+pub fn redundant(ranges: &[ElfRange]) -> Vec<usize> {
+    // Each range covers its own sections, so a section is covered by someone
+    // else exactly when at least two elves cover it
+    let shared = covered_more_than(ranges, 1);
+    ranges
+        .iter()
+        .enumerate()
+        .filter(|(_, range)| shared.iter().any(|segment| segment.contains(range)))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+// Static interval tree: entries sorted by start, laid out as an implicit
+// balanced BST over the sorted array, with the largest end in each subtree
+pub struct IntervalIndex<T> {
+    entries: Vec<(ElfRange, T)>,
+    max_to: Vec<u32>,
+}
+
+impl<T> IntervalIndex<T> {
+    pub fn new(mut entries: Vec<(ElfRange, T)>) -> IntervalIndex<T> {
+        entries.sort_by_key(|(range, _)| *range);
+        let mut max_to = vec![0; entries.len()];
+        fill_max_to(&entries, &mut max_to, 0, entries.len());
+        IntervalIndex { entries, max_to }
+    }
+
+    pub fn stab(&self, section: u32) -> Vec<&(ElfRange, T)> {
+        self.overlapping(&ElfRange {
+            from: section,
+            to: section,
+        })
+    }
+
+    pub fn overlapping(&self, query: &ElfRange) -> Vec<&(ElfRange, T)> {
+        let mut found = vec![];
+        self.collect_overlapping(query, 0, self.entries.len(), &mut found);
+        found
+    }
+
+    fn collect_overlapping<'a>(
+        &'a self,
+        query: &ElfRange,
+        lo: usize,
+        hi: usize,
+        found: &mut Vec<&'a (ElfRange, T)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        // Nothing in this subtree reaches the query
+        if self.max_to[mid] < query.from {
+            return;
+        }
+        self.collect_overlapping(query, lo, mid, found);
+        // Everything from mid onwards starts after the query ends
+        if self.entries[mid].0.from > query.to {
+            return;
+        }
+        if self.entries[mid].0.overlaps(query) {
+            found.push(&self.entries[mid]);
+        }
+        self.collect_overlapping(query, mid + 1, hi, found);
+    }
+}
+
+fn fill_max_to<T>(entries: &[(ElfRange, T)], max_to: &mut [u32], lo: usize, hi: usize) -> u32 {
+    if lo >= hi {
+        return 0;
+    }
+    let mid = (lo + hi) / 2;
+    let left = fill_max_to(entries, max_to, lo, mid);
+    let right = fill_max_to(entries, max_to, mid + 1, hi);
+    max_to[mid] = entries[mid].0.to.max(left).max(right);
+    max_to[mid]
+}
@@ -1,17 +1,21 @@
+mod interval;
+
+use interval::{ElfRange, IntervalIndex};
+use std::env;
 use std::fs::File;
 use std::io::{self, BufRead};
 
-#[derive(Debug, Clone, Copy)]
-struct ElfRange {
-    from: u32,
-    to: u32,
-}
-
-fn parse_range(range_str: &String) -> ElfRange {
+fn parse_range(range_str: &str) -> ElfRange {
     let range_vec: Vec<u32> = range_str
-        .split("-")
+        .split('-')
         .map(|num| num.parse().expect("Bad ID"))
         .collect();
+    assert!(range_vec.len() == 2, "Bad range {}", range_str);
+    assert!(
+        range_vec[0] <= range_vec[1],
+        "Range {} ends before it starts",
+        range_str
+    );
     ElfRange {
         from: range_vec[0],
         to: range_vec[1],
@@ -27,30 +31,145 @@ fn read_lines() -> impl Iterator<Item = (ElfRange, ElfRange)> {
     let lines = io::BufReader::new(file).lines();
     lines
         .map(|line| line.expect("Couldn't read line of input"))
-        .map(move |line| line.split(",").map(|str| String::from(str)).collect())
+        .map(move |line| line.split(',').map(String::from).collect())
         .map(parse_line)
 }
 
 fn is_overlapping(pair: &(ElfRange, ElfRange)) -> bool {
-    if pair.0.to < pair.1.from {
-        false
-    } else if pair.1.to < pair.0.from {
-        false
-    } else {
-        true
-    }
+    pair.0.intersection(&pair.1).is_some()
 }
 
 fn is_contained(pair: &(ElfRange, ElfRange)) -> bool {
-    (pair.0.from <= pair.1.from && pair.0.to >= pair.1.to)
-        || (pair.1.from <= pair.0.from && pair.1.to >= pair.0.to)
+    pair.0.contains(&pair.1) || pair.1.contains(&pair.0)
+}
+
+fn describe_ranges(ranges: &[ElfRange]) -> String {
+    ranges
+        .iter()
+        .map(|range| format!("{}-{}", range.from, range.to))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+// Where an assignment came from; the line number is also the pair number
+#[derive(Debug, Clone, Copy)]
+struct Assignment {
+    line: usize,
+    side: Side,
+}
+
+fn build_index() -> IntervalIndex<Assignment> {
+    let entries = read_lines()
+        .enumerate()
+        .flat_map(|(idx, pair)| {
+            [
+                (
+                    pair.0,
+                    Assignment {
+                        line: idx + 1,
+                        side: Side::Left,
+                    },
+                ),
+                (
+                    pair.1,
+                    Assignment {
+                        line: idx + 1,
+                        side: Side::Right,
+                    },
+                ),
+            ]
+        })
+        .collect();
+    IntervalIndex::new(entries)
+}
+
+fn query_section(section: u32) {
+    let index = build_index();
+    let mut hits = index.stab(section);
+    hits.sort_by_key(|(_, assignment)| assignment.line);
+    println!("{} elves assigned to section {}", hits.len(), section);
+    for (range, assignment) in hits {
+        println!(
+            "  line {} {:?} ({}-{})",
+            assignment.line, assignment.side, range.from, range.to
+        );
+    }
+}
+
+fn query_range(query: ElfRange) {
+    let mut lines: Vec<usize> = build_index()
+        .overlapping(&query)
+        .into_iter()
+        .map(|(_, assignment)| assignment.line)
+        .collect();
+    lines.sort();
+    lines.dedup();
+    println!(
+        "{} pairs overlap sections {}-{}",
+        lines.len(),
+        query.from,
+        query.to
+    );
+    for line in lines {
+        println!("  line {}", line);
+    }
 }
 
 fn main() {
+    let mut args = env::args().skip(1);
+    let k: usize = match args.next() {
+        Some(command) if command == "section" => {
+            let section = args.next().expect("Missing section");
+            query_section(section.parse().expect("Bad ID"));
+            return;
+        }
+        Some(command) if command == "range" => {
+            query_range(parse_range(&args.next().expect("Missing range")));
+            return;
+        }
+        Some(arg) => arg.parse().expect("k should be a non-negative integer"),
+        None => 1,
+    };
+
     let pairs: Vec<(ElfRange, ElfRange)> = read_lines().collect();
-    let number_contained = pairs.iter().filter(|pair| is_contained(&pair)).count();
-    let number_overlapping = pairs.iter().filter(|pair| is_overlapping(&pair)).count();
+    let number_contained = pairs.iter().filter(|pair| is_contained(pair)).count();
+    let number_overlapping = pairs.iter().filter(|pair| is_overlapping(pair)).count();
 
     println!("{}", number_contained);
     println!("{}", number_overlapping);
+
+    // Elf 2i is the left of pair i, elf 2i+1 the right
+    let elves: Vec<ElfRange> = pairs.iter().flat_map(|pair| [pair.0, pair.1]).collect();
+    let coverage = interval::merge(elves.iter().copied());
+    let crowded = interval::covered_more_than(&elves, k);
+    let redundant = interval::redundant(&elves);
+
+    println!("Coverage: {}", describe_ranges(&coverage));
+    println!(
+        "Total sections covered: {}",
+        interval::total_covered(&elves)
+    );
+    println!(
+        "Sections covered by more than {} elves: {} ({})",
+        k,
+        interval::total_covered(&crowded),
+        describe_ranges(&crowded)
+    );
+    println!("Redundant elves: {}", redundant.len());
+    for idx in redundant {
+        let side = if idx % 2 == 0 { "left" } else { "right" };
+        println!(
+            "  pair {} {} ({}-{})",
+            idx / 2 + 1,
+            side,
+            elves[idx].from,
+            elves[idx].to
+        );
+    }
 }
@@ -1,5 +1,7 @@
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
 
 #[derive(Debug, Clone, Copy)]
 struct BatchJob {
@@ -8,49 +10,467 @@ struct BatchJob {
     to: usize,
 }
 
+impl fmt::Display for BatchJob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.count,
+            self.from + 1,
+            self.to + 1
+        )
+    }
+}
+
+#[derive(Debug)]
+enum DrawingError {
+    MissingFooter,
+    BadFooter(String),
+    BadCrate { line: usize, stack: usize },
+    StrayCharacter { line: usize, column: usize },
+    FloatingCrate { line: usize, stack: usize },
+}
+
+impl fmt::Display for DrawingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrawingError::MissingFooter => write!(f, "drawing has no numbered footer"),
+            DrawingError::BadFooter(footer) => {
+                write!(
+                    f,
+                    "footer {:?} should number the stacks 1, 2, 3, ...",
+                    footer
+                )
+            }
+            DrawingError::BadCrate { line, stack } => write!(
+                f,
+                "line {}: stack {} should hold a crate like [A] or be blank",
+                line, stack
+            ),
+            DrawingError::StrayCharacter { line, column } => {
+                write!(
+                    f,
+                    "line {}: unexpected character at column {}",
+                    line, column
+                )
+            }
+            DrawingError::FloatingCrate { line, stack } => write!(
+                f,
+                "line {}: crate in stack {} has nothing underneath it",
+                line, stack
+            ),
+        }
+    }
+}
+
+// Stack i is drawn in columns 4i..4i+2 as "[X]", with the footer number beneath the X
+fn parse_footer(footer: &str) -> Result<usize, DrawingError> {
+    let bad_footer = || DrawingError::BadFooter(footer.to_string());
+    let mut n_stacks = 0;
+    let mut start = None;
+    for (column, c) in footer.chars().chain([' ']).enumerate() {
+        match (start, c.is_ascii_digit()) {
+            (None, true) => start = Some(column),
+            (Some(from), false) => {
+                let label: usize = footer[from..column].parse().map_err(|_| bad_footer())?;
+                let centre = 1 + 4 * n_stacks;
+                if label != n_stacks + 1 || centre < from || centre >= column {
+                    return Err(bad_footer());
+                }
+                n_stacks += 1;
+                start = None;
+            }
+            (None, false) if c != ' ' => return Err(bad_footer()),
+            _ => {}
+        }
+    }
+    if n_stacks == 0 {
+        return Err(bad_footer());
+    }
+    Ok(n_stacks)
+}
+
+fn parse_crate_row(
+    row: &str,
+    line: usize,
+    n_stacks: usize,
+) -> Result<Vec<Option<char>>, DrawingError> {
+    let chars: Vec<char> = row.chars().collect();
+    let at = |column: usize| chars.get(column).copied().unwrap_or(' ');
+    if let Some(column) = (4 * n_stacks..chars.len()).find(|&column| at(column) != ' ') {
+        return Err(DrawingError::StrayCharacter {
+            line,
+            column: column + 1,
+        });
+    }
+    (0..n_stacks)
+        .map(|stack| {
+            let slot = [at(4 * stack), at(4 * stack + 1), at(4 * stack + 2)];
+            if stack + 1 < n_stacks && at(4 * stack + 3) != ' ' {
+                return Err(DrawingError::StrayCharacter {
+                    line,
+                    column: 4 * stack + 4,
+                });
+            }
+            match slot {
+                [' ', ' ', ' '] => Ok(None),
+                ['[', letter, ']'] if letter.is_ascii_alphabetic() => Ok(Some(letter)),
+                _ => Err(DrawingError::BadCrate {
+                    line,
+                    stack: stack + 1,
+                }),
+            }
+        })
+        .collect()
+}
+
+fn parse_drawing(drawing: &[String]) -> Result<Vec<Vec<char>>, DrawingError> {
+    let (footer, rows) = drawing.split_last().ok_or(DrawingError::MissingFooter)?;
+    if !footer
+        .trim_start()
+        .starts_with(|c: char| c.is_ascii_digit())
+    {
+        return Err(DrawingError::MissingFooter);
+    }
+    let n_stacks = parse_footer(footer)?;
+    let mut state: Vec<Vec<char>> = vec![vec![]; n_stacks];
+    // Build each stack from the bottom up
+    for (idx, row) in rows.iter().enumerate().rev() {
+        let line = idx + 1;
+        for (stack, slot) in parse_crate_row(row, line, n_stacks)?
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(letter) = slot {
+                if state[stack].len() != rows.len() - 1 - idx {
+                    return Err(DrawingError::FloatingCrate {
+                        line,
+                        stack: stack + 1,
+                    });
+                }
+                state[stack].push(letter);
+            }
+        }
+    }
+    Ok(state)
+}
+
+// Inverse of parse_drawing, in the same layout as the puzzle input
+fn render_state(state: &[Vec<char>]) -> String {
+    let height = state.iter().map(|stack| stack.len()).max().unwrap_or(0);
+    let mut lines: Vec<String> = (0..height)
+        .rev()
+        .map(|level| {
+            state
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(letter) => format!("[{}]", letter),
+                    None => "   ".to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect();
+    let mut footer: Vec<char> = vec![' '; 4 * state.len()];
+    for stack in 0..state.len() {
+        for (offset, digit) in (stack + 1).to_string().chars().enumerate() {
+            footer[1 + 4 * stack + offset] = digit;
+        }
+    }
+    footer.pop();
+    lines.push(footer.into_iter().collect());
+    lines.join("\n")
+}
+
 fn parse_line(line: Vec<String>) -> BatchJob {
     let count: usize = line[1].parse().expect("Bad number of moves");
     let from: usize = line[3].parse().expect("Bad from bin");
     let to: usize = line[5].parse().expect("Bad to bin");
     BatchJob {
         count,
-        from: from - 1,
-        to: to - 1,
+        from: from.checked_sub(1).expect("Stacks are numbered from 1"),
+        to: to.checked_sub(1).expect("Stacks are numbered from 1"),
     }
 }
 
-fn read_lines() -> impl Iterator<Item = BatchJob> {
+// The drawing is everything above the first blank line, the jobs everything below
+fn read_input() -> (Vec<Vec<char>>, Vec<BatchJob>) {
     let file = File::open("./data/input").expect("Input file not found");
-    let lines = io::BufReader::new(file).lines();
-    lines
-        .map(|line| line.expect("Couldn't read line of input"))
-        .map(move |line| line.split(" ").map(|str| String::from(str)).collect())
+    let mut lines = io::BufReader::new(file)
+        .lines()
+        .map(|line| line.expect("Couldn't read line of input"));
+    let drawing: Vec<String> = lines
+        .by_ref()
+        .take_while(|line| !line.trim().is_empty())
+        .collect();
+    let init_state = parse_drawing(&drawing).unwrap_or_else(|err| panic!("Bad drawing, {}", err));
+    let jobs = lines
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(' ').map(String::from).collect())
         .filter(|line: &Vec<String>| line[0] == "move")
         .map(parse_line)
+        .collect();
+    (init_state, jobs)
 }
 
-fn split_jobs(batch_jobs: impl Iterator<Item = BatchJob>) -> impl Iterator<Item = BatchJob> {
-    batch_jobs
-        .map(|batch_job| {
-            vec![
-                BatchJob {
-                    from: batch_job.from,
-                    to: batch_job.to,
-                    count: 1
-                };
-                batch_job.count
-            ]
-            .into_iter()
-        })
-        .flatten()
+#[derive(Debug)]
+enum MoveError {
+    NoSuchStack(usize),
+    NotEnoughCrates {
+        stack: usize,
+        has: usize,
+        wanted: usize,
+    },
 }
 
-fn do_batch_job(mut state: Vec<Vec<char>>, job: BatchJob) -> Vec<Vec<char>> {
-    let number_in_from_pile = state[job.from].len();
-    let split_point = number_in_from_pile - job.count;
-    let mut split_elements = state[job.from].split_off(split_point);
-    state[job.to].append(&mut split_elements);
-    state
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::NoSuchStack(stack) => write!(f, "there is no stack {}", stack + 1),
+            MoveError::NotEnoughCrates { stack, has, wanted } => write!(
+                f,
+                "stack {} has {} crates but {} were moved",
+                stack + 1,
+                has,
+                wanted
+            ),
+        }
+    }
+}
+
+fn validate_job(state: &[Vec<char>], job: &BatchJob) -> Result<(), MoveError> {
+    for stack in [job.from, job.to] {
+        if stack >= state.len() {
+            return Err(MoveError::NoSuchStack(stack));
+        }
+    }
+    let has = state[job.from].len();
+    if has < job.count {
+        return Err(MoveError::NotEnoughCrates {
+            stack: job.from,
+            has,
+            wanted: job.count,
+        });
+    }
+    Ok(())
+}
+
+// Moves the top `count` crates in one lift, keeping their order
+fn lift(state: &mut [Vec<char>], from: usize, to: usize, count: usize) {
+    let split_point = state[from].len() - count;
+    let mut split_elements = state[from].split_off(split_point);
+    state[to].append(&mut split_elements);
+}
+
+trait Crane {
+    fn name(&self) -> String;
+
+    // How many crates go in each lift when carrying out a job of `count` crates
+    fn lift_sizes(&self, count: usize) -> Vec<usize>;
+
+    fn do_batch_job(&self, state: &mut [Vec<char>], job: BatchJob) -> Result<(), MoveError> {
+        validate_job(state, &job)?;
+        for size in self.lift_sizes(job.count) {
+            lift(state, job.from, job.to, size);
+        }
+        Ok(())
+    }
+
+    // Replays the lifts of `job` in reverse, carrying crates from `to` back to `from`
+    fn undo_batch_job(&self, state: &mut [Vec<char>], job: BatchJob) -> Result<(), MoveError> {
+        let inverse = BatchJob {
+            count: job.count,
+            from: job.to,
+            to: job.from,
+        };
+        validate_job(state, &inverse)?;
+        for size in self.lift_sizes(job.count).into_iter().rev() {
+            lift(state, inverse.from, inverse.to, size);
+        }
+        Ok(())
+    }
+}
+
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn lift_sizes(&self, count: usize) -> Vec<usize> {
+        vec![1; count]
+    }
+}
+
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn lift_sizes(&self, count: usize) -> Vec<usize> {
+        vec![count]
+    }
+}
+
+// Like the 9001, but can only carry `capacity` crates per lift
+struct CappedCrane {
+    capacity: usize,
+}
+
+impl Crane for CappedCrane {
+    fn name(&self) -> String {
+        format!("capacity {} crane", self.capacity)
+    }
+
+    fn lift_sizes(&self, count: usize) -> Vec<usize> {
+        let mut sizes = vec![self.capacity; count / self.capacity];
+        if !count.is_multiple_of(self.capacity) {
+            sizes.push(count % self.capacity);
+        }
+        sizes
+    }
+}
+
+fn run_crane(
+    crane: &dyn Crane,
+    mut state: Vec<Vec<char>>,
+    jobs: &[BatchJob],
+) -> Result<Vec<Vec<char>>, String> {
+    for (idx, job) in jobs.iter().enumerate() {
+        crane
+            .do_batch_job(&mut state, *job)
+            .map_err(|err| format!("{} failed on move {}: {}", crane.name(), idx + 1, err))?;
+    }
+    Ok(state)
+}
+
+// Steps a crane back and forth through the jobs, `done` of which have been applied
+struct Replay<'a> {
+    crane: &'a dyn Crane,
+    jobs: &'a [BatchJob],
+    state: Vec<Vec<char>>,
+    done: usize,
+}
+
+impl<'a> Replay<'a> {
+    fn new(crane: &'a dyn Crane, init_state: Vec<Vec<char>>, jobs: &'a [BatchJob]) -> Replay<'a> {
+        Replay {
+            crane,
+            jobs,
+            state: init_state,
+            done: 0,
+        }
+    }
+
+    fn step_forward(&mut self) -> Result<(), String> {
+        let job = *self.jobs.get(self.done).ok_or("Already at the last move")?;
+        self.crane
+            .do_batch_job(&mut self.state, job)
+            .map_err(|err| format!("Move {} failed: {}", self.done + 1, err))?;
+        self.done += 1;
+        Ok(())
+    }
+
+    fn step_back(&mut self) -> Result<(), String> {
+        if self.done == 0 {
+            return Err("Already at the initial state".to_string());
+        }
+        let job = self.jobs[self.done - 1];
+        self.crane
+            .undo_batch_job(&mut self.state, job)
+            .map_err(|err| format!("Undoing move {} failed: {}", self.done, err))?;
+        self.done -= 1;
+        Ok(())
+    }
+
+    fn jump_to(&mut self, target: usize) -> Result<(), String> {
+        if target > self.jobs.len() {
+            return Err(format!("There are only {} moves", self.jobs.len()));
+        }
+        while self.done < target {
+            self.step_forward()?;
+        }
+        while self.done > target {
+            self.step_back()?;
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> String {
+        let header = match self.done {
+            0 => "Initial state".to_string(),
+            n => format!(
+                "After move {} of {} ({})",
+                n,
+                self.jobs.len(),
+                self.jobs[n - 1]
+            ),
+        };
+        format!("{}\n{}\n", header, render_state(&self.state))
+    }
+}
+
+fn write_transcript(replay: &mut Replay, path: &str) {
+    let mut transcript = replay.render();
+    while replay.done < replay.jobs.len() {
+        if let Err(err) = replay.step_forward() {
+            transcript.push_str(&format!("{}\n", err));
+            break;
+        }
+        transcript.push('\n');
+        transcript.push_str(&replay.render());
+    }
+    fs::write(path, transcript).expect("Couldn't write transcript");
+}
+
+fn parse_crane(name: &str) -> Box<dyn Crane> {
+    match name {
+        "9000" => Box::new(CrateMover9000),
+        "9001" => Box::new(CrateMover9001),
+        other => {
+            let capacity: usize = other
+                .strip_prefix("cap")
+                .and_then(|capacity| capacity.parse().ok())
+                .filter(|&capacity| capacity > 0)
+                .expect("Crane should be 9000, 9001 or capN");
+            Box::new(CappedCrane { capacity })
+        }
+    }
+}
+
+fn replay(crane: &dyn Crane, init_state: Vec<Vec<char>>, jobs: &[BatchJob]) {
+    let mut replay = Replay::new(crane, init_state, jobs);
+    println!("{}", replay.render());
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Couldn't flush stdout");
+        let mut command = String::new();
+        if io::stdin()
+            .read_line(&mut command)
+            .expect("Couldn't read command")
+            == 0
+        {
+            return;
+        }
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let result = match words.as_slice() {
+            [] | ["n"] | ["next"] => replay.step_forward(),
+            ["b"] | ["back"] => replay.step_back(),
+            ["g", n] | ["goto", n] => match n.parse() {
+                Ok(target) => replay.jump_to(target),
+                Err(_) => Err(format!("Bad move number {}", n)),
+            },
+            ["q"] | ["quit"] => return,
+            _ => Err("Commands are next, back, goto N and quit".to_string()),
+        };
+        match result {
+            Ok(()) => println!("{}", replay.render()),
+            Err(err) => println!("{}", err),
+        }
+    }
 }
 
 fn get_final_letters(state: Vec<Vec<char>>) -> String {
@@ -61,26 +481,36 @@ fn get_final_letters(state: Vec<Vec<char>>) -> String {
 }
 
 fn main() {
-    let init_state: Vec<Vec<char>> = vec![
-        vec!['S', 'M', 'R', 'N', 'W', 'J', 'V', 'T'],
-        vec!['B', 'W', 'D', 'J', 'Q', 'P', 'C', 'V'],
-        vec!['B', 'J', 'F', 'H', 'D', 'R', 'P'],
-        vec!['F', 'R', 'P', 'B', 'M', 'N', 'D'],
-        vec!['H', 'V', 'R', 'P', 'T', 'B'],
-        vec!['C', 'B', 'P', 'T'],
-        vec!['B', 'J', 'R', 'P', 'L'],
-        vec!['N', 'C', 'S', 'L', 'T', 'Z', 'B', 'W'],
-        vec!['L', 'S', 'G'],
-    ];
-
-    let batch_jobs = read_lines();
-    let jobs = split_jobs(batch_jobs);
-    let final_state_a = jobs.fold(init_state.clone(), do_batch_job);
-    let final_letters_a = get_final_letters(final_state_a);
-    println!("{}", final_letters_a);
-
-    let batch_jobs = read_lines();
-    let final_state_b = batch_jobs.fold(init_state, do_batch_job);
-    let final_letters_b = get_final_letters(final_state_b);
-    println!("{}", final_letters_b);
+    let mut args = env::args().skip(1);
+    let first_arg = args.next();
+    let (init_state, batch_jobs) = read_input();
+
+    if first_arg.as_deref() == Some("replay") {
+        let crane = parse_crane(&args.next().unwrap_or_else(|| "9001".to_string()));
+        if let Some(path) = args.next() {
+            let mut transcript_replay =
+                Replay::new(crane.as_ref(), init_state.clone(), &batch_jobs);
+            write_transcript(&mut transcript_replay, &path);
+        }
+        replay(crane.as_ref(), init_state, &batch_jobs);
+        return;
+    }
+
+    let capacity: Option<usize> = first_arg.map(|arg| {
+        arg.parse()
+            .expect("Crane capacity should be a positive integer")
+    });
+
+    let mut cranes: Vec<Box<dyn Crane>> = vec![Box::new(CrateMover9000), Box::new(CrateMover9001)];
+    if let Some(capacity) = capacity {
+        assert!(capacity > 0, "Crane capacity should be a positive integer");
+        cranes.push(Box::new(CappedCrane { capacity }));
+    }
+
+    for crane in cranes {
+        match run_crane(crane.as_ref(), init_state.clone(), &batch_jobs) {
+            Ok(final_state) => println!("{}", get_final_letters(final_state)),
+            Err(err) => println!("{}", err),
+        }
+    }
 }
@@ -1,31 +1,264 @@
-use itertools::Itertools;
+use std::collections::HashMap;
+use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn read_file() -> Vec<char> {
-    let file = File::open("./data/input").unwrap();
-    let mut buffer = BufReader::new(file);
-    let mut first_line = String::new();
-    let _ = buffer.read_line(&mut first_line);
-    first_line.trim().chars().collect()
+fn open_input() -> BufReader<File> {
+    let file = File::open("./data/input").expect("Input file not found");
+    BufReader::new(file)
 }
 
-fn find_packet(chars: &Vec<char>, w_size: usize) -> usize {
-    let first_marker = chars
-        .as_slice()
-        .windows(w_size)
-        .enumerate()
-        .find(|(_, window)| all_unique(window, w_size))
-        .unwrap();
-    first_marker.0 + w_size
+// Remembers the last position at which each symbol was seen
+trait SeenTable<S> {
+    fn replace(&mut self, symbol: S, position: usize) -> Option<usize>;
 }
 
-fn all_unique(window: &[char], w_size: usize) -> bool {
-    window.to_vec().iter().unique().count() == w_size
+impl SeenTable<u8> for [Option<usize>; 256] {
+    fn replace(&mut self, symbol: u8, position: usize) -> Option<usize> {
+        self[symbol as usize].replace(position)
+    }
+}
+
+impl SeenTable<char> for HashMap<char, usize> {
+    fn replace(&mut self, symbol: char, position: usize) -> Option<usize> {
+        self.insert(symbol, position)
+    }
+}
+
+trait Symbol: Copy + Sized {
+    type Table: SeenTable<Self>;
+
+    fn new_table() -> Self::Table;
+
+    fn is_line_end(self) -> bool;
+
+    // Decodes as much of `bytes` as possible, returning the symbols and how many
+    // bytes they used; at the end of the stream everything must be decoded
+    fn decode(bytes: &[u8], at_end: bool) -> (Vec<Self>, usize);
+}
+
+impl Symbol for u8 {
+    type Table = [Option<usize>; 256];
+
+    fn new_table() -> Self::Table {
+        [None; 256]
+    }
+
+    fn is_line_end(self) -> bool {
+        self == b'\n' || self == b'\r'
+    }
+
+    fn decode(bytes: &[u8], _at_end: bool) -> (Vec<u8>, usize) {
+        (bytes.to_vec(), bytes.len())
+    }
+}
+
+impl Symbol for char {
+    type Table = HashMap<char, usize>;
+
+    fn new_table() -> Self::Table {
+        HashMap::new()
+    }
+
+    fn is_line_end(self) -> bool {
+        self == '\n' || self == '\r'
+    }
+
+    fn decode(bytes: &[u8], at_end: bool) -> (Vec<char>, usize) {
+        let mut symbols = vec![];
+        let mut used = 0;
+        while used < bytes.len() {
+            match std::str::from_utf8(&bytes[used..]) {
+                Ok(valid) => {
+                    symbols.extend(valid.chars());
+                    used = bytes.len();
+                }
+                Err(err) => {
+                    let valid_up_to = used + err.valid_up_to();
+                    let valid = std::str::from_utf8(&bytes[used..valid_up_to]).unwrap();
+                    symbols.extend(valid.chars());
+                    used = valid_up_to;
+                    match err.error_len() {
+                        Some(len) => {
+                            symbols.push(char::REPLACEMENT_CHARACTER);
+                            used += len;
+                        }
+                        // Truncated sequence, wait for the rest of it
+                        None if !at_end => break,
+                        None => {
+                            symbols.push(char::REPLACEMENT_CHARACTER);
+                            used = bytes.len();
+                        }
+                    }
+                }
+            }
+        }
+        (symbols, used)
+    }
+}
+
+// Symbols of a stream, decoded one buffered chunk at a time
+struct SymbolStream<R, S> {
+    reader: R,
+    carry: Vec<u8>,
+    decoded: std::vec::IntoIter<S>,
+    finished: bool,
+}
+
+impl<R: BufRead, S: Symbol> SymbolStream<R, S> {
+    fn new(reader: R) -> SymbolStream<R, S> {
+        SymbolStream {
+            reader,
+            carry: vec![],
+            decoded: vec![].into_iter(),
+            finished: false,
+        }
+    }
+}
+
+impl<R: BufRead, S: Symbol> Iterator for SymbolStream<R, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<S> {
+        loop {
+            if let Some(symbol) = self.decoded.next() {
+                return Some(symbol);
+            }
+            if self.finished {
+                return None;
+            }
+            let chunk = self.reader.fill_buf().expect("Couldn't read input");
+            let chunk_len = chunk.len();
+            self.finished = chunk_len == 0;
+            self.carry.extend_from_slice(chunk);
+            self.reader.consume(chunk_len);
+            let (symbols, used) = S::decode(&self.carry, self.finished);
+            self.carry.drain(..used);
+            self.decoded = symbols.into_iter();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Marker {
+    w_size: usize,
+    // Number of symbols read when the marker completes
+    end: usize,
+}
+
+// Every position at which the last w symbols are all distinct, for each w in
+// w_sizes. The run of distinct symbols ending at a position doesn't depend on
+// w, so all sizes share one O(1)-per-symbol scan
+struct Markers<I, S: Symbol> {
+    symbols: I,
+    w_sizes: Vec<usize>,
+    last_seen: S::Table,
+    run_start: usize,
+    position: usize,
+    pending: Vec<Marker>,
+}
+
+impl<I: Iterator<Item = S>, S: Symbol> Iterator for Markers<I, S> {
+    type Item = Marker;
+
+    fn next(&mut self) -> Option<Marker> {
+        while self.pending.is_empty() {
+            let symbol = self.symbols.next()?;
+            if let Some(previous) = self.last_seen.replace(symbol, self.position) {
+                self.run_start = self.run_start.max(previous + 1);
+            }
+            self.position += 1;
+            let run_length = self.position - self.run_start;
+            // Reversed so that pop hands them out in w_sizes order
+            self.pending.extend(
+                self.w_sizes
+                    .iter()
+                    .rev()
+                    .filter(|&&w_size| run_length >= w_size)
+                    .map(|&w_size| Marker {
+                        w_size,
+                        end: self.position,
+                    }),
+            );
+        }
+        self.pending.pop()
+    }
+}
+
+fn find_markers<S: Symbol>(
+    symbols: impl Iterator<Item = S>,
+    w_sizes: &[usize],
+) -> Markers<impl Iterator<Item = S>, S> {
+    assert!(
+        w_sizes.iter().all(|&w_size| w_size > 0),
+        "Window sizes should be positive"
+    );
+    Markers {
+        symbols: symbols.take_while(|symbol| !symbol.is_line_end()),
+        w_sizes: w_sizes.to_vec(),
+        last_seen: S::new_table(),
+        run_start: 0,
+        position: 0,
+        pending: vec![],
+    }
+}
+
+// First marker of each window size, stopping the scan once all are found
+fn find_first_markers(
+    markers: impl Iterator<Item = Marker>,
+    w_sizes: &[usize],
+) -> Vec<Option<usize>> {
+    let mut firsts: Vec<Option<usize>> = vec![None; w_sizes.len()];
+    for marker in markers {
+        for (idx, &w_size) in w_sizes.iter().enumerate() {
+            if w_size == marker.w_size && firsts[idx].is_none() {
+                firsts[idx] = Some(marker.end);
+            }
+        }
+        if firsts.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    firsts
+}
+
+fn report<S: Symbol>(w_sizes: &[usize], all: bool) {
+    let markers = find_markers(SymbolStream::<_, S>::new(open_input()), w_sizes);
+    if all {
+        for marker in markers {
+            println!("{} {}", marker.w_size, marker.end);
+        }
+        return;
+    }
+    for (w_size, first) in w_sizes.iter().zip(find_first_markers(markers, w_sizes)) {
+        match first {
+            Some(end) => println!("{}", end),
+            None => println!("No marker of size {}", w_size),
+        }
+    }
 }
 
 fn main() {
-    let chars = read_file();
-    println!("{}", find_packet(&chars, 4));
-    println!("{}", find_packet(&chars, 14));
+    let mut all = false;
+    let mut chars = false;
+    let mut w_sizes: Vec<usize> = vec![];
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--chars" => chars = true,
+            size => w_sizes.push(
+                size.parse()
+                    .expect("Window size should be a positive integer"),
+            ),
+        }
+    }
+    if w_sizes.is_empty() {
+        w_sizes = vec![4, 14];
+    }
+
+    if chars {
+        report::<char>(&w_sizes, all);
+    } else {
+        report::<u8>(&w_sizes, all);
+    }
 }
@@ -0,0 +1,132 @@
+use super::{fill_dir_sizes, node_size, valid_name, ArenaTree, ElfFile, ElfType, VfsError};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Nested as {name, type, size, children}, with directories carrying their total size
+pub fn to_json(tree: &ArenaTree<ElfFile>, idx: usize) -> Value {
+    let node = tree.get_node(idx);
+    match node.val.t {
+        ElfType::File => json!({
+            "name": node.val.name,
+            "type": "file",
+            "size": node_size(node),
+        }),
+        ElfType::Dir => json!({
+            "name": node.val.name,
+            "type": "dir",
+            "size": node_size(node),
+            "children": node
+                .children
+                .iter()
+                .map(|&child| to_json(tree, child))
+                .collect::<Vec<Value>>(),
+        }),
+    }
+}
+
+fn bad_json(reason: &str) -> VfsError {
+    VfsError::BadJson(reason.to_string())
+}
+
+fn parse_entry(value: &Value) -> Result<ElfFile, VfsError> {
+    let name = value["name"]
+        .as_str()
+        .ok_or_else(|| bad_json("entry without a name"))?;
+    if !valid_name(name) {
+        return Err(VfsError::BadJson(format!("bad entry name {:?}", name)));
+    }
+    let t = match value["type"].as_str() {
+        Some("file") => ElfType::File,
+        Some("dir") => ElfType::Dir,
+        _ => return Err(VfsError::BadJson(format!("{} has no valid type", name))),
+    };
+    // Directory sizes are recomputed rather than trusted
+    let size = match t {
+        ElfType::File => Some(
+            value["size"]
+                .as_u64()
+                .and_then(|size| u32::try_from(size).ok())
+                .ok_or_else(|| VfsError::BadJson(format!("{} has no valid size", name)))?,
+        ),
+        ElfType::Dir => None,
+    };
+    Ok(ElfFile {
+        name: name.to_string(),
+        t,
+        size,
+    })
+}
+
+fn import_children(
+    tree: &mut ArenaTree<ElfFile>,
+    parent: usize,
+    value: &Value,
+) -> Result<(), VfsError> {
+    let children = match value.get("children") {
+        None => return Ok(()),
+        Some(children) => children
+            .as_array()
+            .ok_or_else(|| bad_json("children should be an array"))?,
+    };
+    let mut seen = HashSet::new();
+    for child in children {
+        let listing = parse_entry(child)?;
+        let name = listing.name.clone();
+        // Re-listing is fine in terminal output, but here it would merge entries
+        if !seen.insert(name.clone()) {
+            return Err(VfsError::BadJson(format!("{} appears twice", name)));
+        }
+        tree.handle_listing(parent, listing)?;
+        let idx = tree.find_child(parent, &name).unwrap();
+        if tree.get_node(idx).val.t == ElfType::Dir {
+            import_children(tree, idx, child)?;
+        } else if child.get("children").is_some() {
+            return Err(VfsError::BadJson(format!("file {} has children", name)));
+        }
+    }
+    Ok(())
+}
+
+pub fn from_json(value: &Value) -> Result<ArenaTree<ElfFile>, VfsError> {
+    if value["type"].as_str() != Some("dir") {
+        return Err(bad_json("the root should be a directory"));
+    }
+    let mut tree = ArenaTree::new_filesystem();
+    import_children(&mut tree, 0, value)?;
+    fill_dir_sizes(&mut tree);
+    Ok(tree)
+}
+
+// Files are created sparse, so check them with `du --apparent-size` or `find -size`
+fn materialise_node(tree: &ArenaTree<ElfFile>, idx: usize, path: &Path) {
+    let node = tree.get_node(idx);
+    match node.val.t {
+        ElfType::Dir => {
+            fs::create_dir_all(path).expect("Couldn't create directory");
+            for &child in node.children.iter() {
+                let child_path = path.join(&tree.get_node(child).val.name);
+                materialise_node(tree, child, &child_path);
+            }
+        }
+        ElfType::File => {
+            let file = File::create(path).expect("Couldn't create file");
+            file.set_len(node_size(node) as u64)
+                .expect("Couldn't size file");
+        }
+    }
+}
+
+pub fn materialise(tree: &ArenaTree<ElfFile>, target: Option<PathBuf>) -> PathBuf {
+    let root = target.unwrap_or_else(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("elf_fs_{}_{}", std::process::id(), nanos))
+    });
+    materialise_node(tree, 0, &root);
+    root
+}
@@ -1,5 +1,10 @@
-use std::fs::File;
+mod export;
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{self, BufRead};
+use std::path::PathBuf;
 
 #[derive(Debug)]
 struct Node<T> {
@@ -27,7 +32,7 @@ impl<T> ArenaTree<T> {
         if let Some(parent_idx) = parent {
             self.arena[parent_idx].children.push(new_idx);
         }
-        return new_idx;
+        new_idx
     }
 
     fn get_node(&self, idx: usize) -> &Node<T> {
@@ -35,13 +40,12 @@ impl<T> ArenaTree<T> {
     }
 
     fn get_parent(&self, idx: usize) -> Option<usize> {
-        self.arena[idx].parent.clone()
+        self.arena[idx].parent
     }
 }
 
 #[derive(Debug)]
 enum ElfOperation {
-    MoveUp,
     ListFiles,
     ChangeDirectory(String),
 }
@@ -52,7 +56,7 @@ enum ParsedLine {
     Listing(ElfFile),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 enum ElfType {
     File,
     Dir,
@@ -68,10 +72,7 @@ pub struct ElfFile {
 fn parse_operation(line: Vec<String>) -> ParsedLine {
     ParsedLine::Operation(match line[1].as_str() {
         "ls" => ElfOperation::ListFiles,
-        "cd" => match line[2].as_str() {
-            ".." => ElfOperation::MoveUp,
-            _ => ElfOperation::ChangeDirectory(line[2].clone()),
-        },
+        "cd" => ElfOperation::ChangeDirectory(line[2].clone()),
         _ => panic!(),
     })
 }
@@ -101,7 +102,7 @@ fn parse_line(line: Vec<String>) -> ParsedLine {
     }
 }
 
-fn read_lines<'a>() -> Vec<ParsedLine> {
+fn read_lines() -> Vec<ParsedLine> {
     let file = File::open("./data/input").unwrap();
     let lines = io::BufReader::new(file).lines();
     lines
@@ -110,83 +111,688 @@ fn read_lines<'a>() -> Vec<ParsedLine> {
         .collect()
 }
 
-fn handle_listing(tree: &mut ArenaTree<ElfFile>, working_node: usize, listing: ElfFile) {
-    tree.add_node(listing, Some(working_node));
+#[derive(Debug)]
+enum VfsError {
+    AboveRoot,
+    NotADirectory(String),
+    NoSuchEntry(String),
+    ConflictingListing { path: String, listing: ElfFile },
+    BadName(String),
+    BadJson(String),
 }
 
-fn build_tree(lines: Vec<ParsedLine>) -> ArenaTree<ElfFile> {
-    let mut tree: ArenaTree<ElfFile> = ArenaTree { arena: vec![] };
-    let mut working_node = tree.add_node(
-        ElfFile {
-            name: "/".to_string(),
-            t: ElfType::Dir,
-            size: None,
-        },
-        None,
-    );
+impl fmt::Display for VfsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VfsError::AboveRoot => write!(f, "cannot move above /"),
+            VfsError::NotADirectory(path) => write!(f, "{} is not a directory", path),
+            VfsError::NoSuchEntry(path) => write!(f, "{} does not exist", path),
+            VfsError::ConflictingListing { path, listing } => write!(
+                f,
+                "{} was listed as {:?} {:?} but already exists as something else",
+                path, listing.t, listing.size
+            ),
+            VfsError::BadName(name) => write!(f, "{:?} is not a valid entry name", name),
+            VfsError::BadJson(reason) => write!(f, "bad JSON export, {}", reason),
+        }
+    }
+}
+
+// Entry names become path components when a tree is materialised
+fn valid_name(name: &str) -> bool {
+    !(name.is_empty() || name.contains('/') || name == "." || name == "..")
+}
+
+impl ArenaTree<ElfFile> {
+    fn new_filesystem() -> ArenaTree<ElfFile> {
+        let mut tree = ArenaTree { arena: vec![] };
+        tree.add_node(
+            ElfFile {
+                name: "/".to_string(),
+                t: ElfType::Dir,
+                size: None,
+            },
+            None,
+        );
+        tree
+    }
+
+    fn find_child(&self, idx: usize, name: &str) -> Option<usize> {
+        self.get_node(idx)
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.get_node(child).val.name == name)
+    }
+
+    fn path_of(&self, idx: usize) -> String {
+        match self.get_parent(idx) {
+            None => "/".to_string(),
+            Some(0) => format!("/{}", self.get_node(idx).val.name),
+            Some(parent) => format!("{}/{}", self.path_of(parent), self.get_node(idx).val.name),
+        }
+    }
+
+    // Follows an absolute or relative path from `start`, as `cd` does.
+    // Directories that haven't been listed yet are created along the way
+    fn walk(&mut self, start: usize, path: &str) -> Result<usize, VfsError> {
+        let mut current = if path.starts_with('/') { 0 } else { start };
+        for segment in path.split('/') {
+            current = match segment {
+                "" | "." => current,
+                ".." => self.get_parent(current).ok_or(VfsError::AboveRoot)?,
+                name => match self.find_child(current, name) {
+                    Some(child) => child,
+                    None => self.add_node(
+                        ElfFile {
+                            name: name.to_string(),
+                            t: ElfType::Dir,
+                            size: None,
+                        },
+                        Some(current),
+                    ),
+                },
+            };
+            if self.get_node(current).val.t != ElfType::Dir {
+                return Err(VfsError::NotADirectory(self.path_of(current)));
+            }
+        }
+        Ok(current)
+    }
+
+    fn lookup(&self, path: &str) -> Result<usize, VfsError> {
+        let mut current = 0;
+        for segment in path.split('/') {
+            current = match segment {
+                "" | "." => current,
+                ".." => self.get_parent(current).ok_or(VfsError::AboveRoot)?,
+                name => self
+                    .find_child(current, name)
+                    .ok_or_else(|| VfsError::NoSuchEntry(path.to_string()))?,
+            };
+        }
+        Ok(current)
+    }
+
+    // Re-listing a directory is harmless, but an entry may not change its type or size
+    fn handle_listing(&mut self, working_node: usize, listing: ElfFile) -> Result<(), VfsError> {
+        if !valid_name(&listing.name) {
+            return Err(VfsError::BadName(listing.name));
+        }
+        match self.find_child(working_node, &listing.name) {
+            None => {
+                self.add_node(listing, Some(working_node));
+                Ok(())
+            }
+            Some(existing) if self.get_node(existing).val == listing => Ok(()),
+            Some(existing) => Err(VfsError::ConflictingListing {
+                path: self.path_of(existing),
+                listing,
+            }),
+        }
+    }
+}
+
+fn build_tree(lines: Vec<ParsedLine>) -> Result<ArenaTree<ElfFile>, VfsError> {
+    let mut tree = ArenaTree::new_filesystem();
+    let mut working_node = 0;
     for line in lines {
         match line {
             ParsedLine::Operation(op) => match op {
-                ElfOperation::MoveUp => {
-                    working_node = tree.get_parent(working_node).unwrap();
-                }
                 ElfOperation::ListFiles => {
                     // Nothing to do
                 }
-                ElfOperation::ChangeDirectory(dir_name) => {
-                    if dir_name == "/" {
-                        working_node = 0;
-                        continue;
-                    }
-                    let children = &tree.get_node(working_node).children;
-                    let new_working_node = children
-                        .iter()
-                        .find(|&idx| tree.get_node(*idx).val.name == dir_name)
-                        .unwrap();
-                    working_node = *new_working_node;
+                ElfOperation::ChangeDirectory(path) => {
+                    working_node = tree.walk(working_node, &path)?;
                 }
             },
-            ParsedLine::Listing(listing) => handle_listing(&mut tree, working_node, listing),
+            ParsedLine::Listing(listing) => tree.handle_listing(working_node, listing)?,
         }
     }
-    return tree;
+    Ok(tree)
 }
 
-fn compute_node_size(tree: &ArenaTree<ElfFile>, node: &Node<ElfFile>) -> u32 {
-    if let Some(size) = node.val.size {
-        size
+// Directories are listed without a size, so fill in their totals bottom-up.
+// Children are always added after their parent, so a reverse sweep of the
+// arena sees every node before its parent
+fn fill_dir_sizes(tree: &mut ArenaTree<ElfFile>) {
+    let mut totals = vec![0; tree.arena.len()];
+    for idx in (0..tree.arena.len()).rev() {
+        let node = &mut tree.arena[idx];
+        let total = match node.val.t {
+            ElfType::File => node.val.size.unwrap(),
+            ElfType::Dir => totals[idx],
+        };
+        node.val.size = Some(total);
+        if let Some(parent) = node.parent {
+            totals[parent] += total;
+        }
+    }
+}
+
+fn node_size(node: &Node<ElfFile>) -> u32 {
+    node.val
+        .size
+        .expect("Sizes should be filled in after building")
+}
+
+// Every directory under `root` with its full path and total size, like `du`
+fn du(tree: &ArenaTree<ElfFile>, root: usize) {
+    let node = tree.get_node(root);
+    if node.val.t != ElfType::Dir {
+        return;
+    }
+    for &child in node.children.iter() {
+        du(tree, child);
+    }
+    println!("{}\t{}", node_size(node), tree.path_of(root));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortOrder {
+    Listing,
+    Name,
+    Size,
+}
+
+struct TreeOptions {
+    sort: SortOrder,
+    max_depth: Option<usize>,
+    human_readable: bool,
+}
+
+fn human_size(size: u32) -> String {
+    let units = ["B", "K", "M", "G"];
+    let mut scaled = size as f64;
+    let mut unit = 0;
+    while scaled >= 1024.0 && unit + 1 < units.len() {
+        scaled /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", size)
     } else {
-        node.children
+        format!("{:.1}{}", scaled, units[unit])
+    }
+}
+
+fn render_tree(tree: &ArenaTree<ElfFile>, root: usize, options: &TreeOptions) -> Vec<String> {
+    let mut lines = vec![];
+    render_subtree(tree, root, options, "", 0, &mut lines);
+    lines
+}
+
+fn render_subtree(
+    tree: &ArenaTree<ElfFile>,
+    idx: usize,
+    options: &TreeOptions,
+    prefix: &str,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    if options
+        .max_depth
+        .is_some_and(|max_depth| depth >= max_depth)
+    {
+        return;
+    }
+    let mut children = tree.get_node(idx).children.clone();
+    match options.sort {
+        SortOrder::Listing => {}
+        SortOrder::Name => {
+            children.sort_by(|&a, &b| tree.get_node(a).val.name.cmp(&tree.get_node(b).val.name))
+        }
+        SortOrder::Size => {
+            children.sort_by_key(|&child| std::cmp::Reverse(node_size(tree.get_node(child))))
+        }
+    }
+    for (position, &child) in children.iter().enumerate() {
+        let last = position + 1 == children.len();
+        let (branch, extension) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        lines.push(format!(
+            "{}{}{}",
+            prefix,
+            branch,
+            describe_node(tree.get_node(child), options)
+        ));
+        let child_prefix = format!("{}{}", prefix, extension);
+        render_subtree(tree, child, options, &child_prefix, depth + 1, lines);
+    }
+}
+
+fn format_size(size: u32, options: &TreeOptions) -> String {
+    if options.human_readable {
+        human_size(size)
+    } else {
+        size.to_string()
+    }
+}
+
+fn describe_node(node: &Node<ElfFile>, options: &TreeOptions) -> String {
+    let size = format_size(node_size(node), options);
+    match node.val.t {
+        ElfType::Dir => format!("{}/ ({})", node.val.name, size),
+        ElfType::File => format!("{} ({})", node.val.name, size),
+    }
+}
+
+fn parse_tree_options(args: impl Iterator<Item = String>) -> (String, TreeOptions) {
+    let mut path = "/".to_string();
+    let mut options = TreeOptions {
+        sort: SortOrder::Listing,
+        max_depth: None,
+        human_readable: false,
+    };
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sort" => {
+                options.sort = match args.next().as_deref() {
+                    Some("name") => SortOrder::Name,
+                    Some("size") => SortOrder::Size,
+                    _ => panic!("--sort should be name or size"),
+                }
+            }
+            "--depth" => {
+                options.max_depth = Some(
+                    args.next()
+                        .and_then(|depth| depth.parse().ok())
+                        .expect("--depth should be a number"),
+                )
+            }
+            "-h" => options.human_readable = true,
+            other => path = other.to_string(),
+        }
+    }
+    (path, options)
+}
+
+// Smallest directory that frees at least `need` on its own
+fn smallest_single_dir(tree: &ArenaTree<ElfFile>, need: u32) -> Option<usize> {
+    (0..tree.arena.len())
+        .filter(|&idx| tree.get_node(idx).val.t == ElfType::Dir)
+        .filter(|&idx| node_size(tree.get_node(idx)) >= need)
+        .min_by_key(|&idx| node_size(tree.get_node(idx)))
+}
+
+// First total in `start..end` that is set, a word at a time
+fn first_set_from(bits: &[u64], start: usize, end: usize) -> Option<usize> {
+    let mut word_idx = start / 64;
+    let mut word = bits.get(word_idx)? & (u64::MAX << (start % 64));
+    loop {
+        if word != 0 {
+            let total = word_idx * 64 + word.trailing_zeros() as usize;
+            return Some(total).filter(|&total| total < end);
+        }
+        word_idx += 1;
+        word = *bits.get(word_idx)?;
+    }
+}
+
+// Subset sum over the tree, where a set of directories is valid if none is
+// nested in another. The directories are visited depth first, threading the
+// totals reachable so far; a directory can be added to any total that was
+// reachable before its subtree was visited. Totals below `need` are kept as a
+// bitset, and of the rest only the smallest, since larger ones never help
+struct DeletionPlanner<'a> {
+    tree: &'a ArenaTree<ElfFile>,
+    need: u32,
+    reachable: Vec<u64>,
+    // The directory whose deletion first made each total below `need` reachable
+    first_by: Vec<u32>,
+    // The smallest total at or above `need`, the directory deleted last to
+    // reach it, and the total below `need` it was added to
+    best: Option<(u32, usize, usize)>,
+}
+
+impl<'a> DeletionPlanner<'a> {
+    fn new(tree: &'a ArenaTree<ElfFile>, need: u32) -> DeletionPlanner<'a> {
+        let mut planner = DeletionPlanner {
+            tree,
+            need,
+            reachable: vec![],
+            first_by: vec![],
+            best: None,
+        };
+        // Deleting everything can't free enough, so don't size the tables
+        if need == 0 || need > node_size(tree.get_node(0)) {
+            return planner;
+        }
+        planner.reachable = vec![0; (need as usize).div_ceil(64)];
+        planner.first_by = vec![u32::MAX; need as usize];
+        planner.reachable[0] = 1;
+        planner.fill(0);
+        planner
+    }
+
+    fn child_dirs(&self, idx: usize) -> Vec<usize> {
+        self.tree
+            .get_node(idx)
+            .children
             .iter()
-            .map(|idx| compute_node_size(tree, tree.get_node(*idx)))
-            .sum()
+            .copied()
+            .filter(|&child| self.tree.get_node(child).val.t == ElfType::Dir)
+            .collect()
+    }
+
+    fn fill(&mut self, idx: usize) {
+        let before = self.reachable.clone();
+        for child in self.child_dirs(idx) {
+            self.fill(child);
+        }
+        self.add_deletion(idx, &before);
+    }
+
+    // Adds `idx` to every total in `before`, recording the totals it makes reachable
+    fn add_deletion(&mut self, idx: usize, before: &[u64]) {
+        let need = self.need as usize;
+        let size = node_size(self.tree.get_node(idx)) as usize;
+        if size < need {
+            let (word_shift, bit_shift) = (size / 64, size % 64);
+            for from in (0..before.len() - word_shift).rev() {
+                let to = from + word_shift;
+                let mut shifted = [before[from] << bit_shift, 0];
+                if bit_shift > 0 {
+                    shifted[1] = before[from] >> (64 - bit_shift);
+                }
+                for (offset, &bits) in shifted.iter().enumerate() {
+                    let Some(word) = self.reachable.get_mut(to + offset) else {
+                        continue;
+                    };
+                    let mut fresh = bits & !*word;
+                    *word |= fresh;
+                    while fresh != 0 {
+                        let total = (to + offset) * 64 + fresh.trailing_zeros() as usize;
+                        if total < need {
+                            self.first_by[total] = idx as u32;
+                        }
+                        fresh &= fresh - 1;
+                    }
+                }
+            }
+            // Bits past `need` in the last word aren't totals we track
+            if !need.is_multiple_of(64) {
+                *self.reachable.last_mut().unwrap() &= (1 << (need % 64)) - 1;
+            }
+        }
+        // A total already at or above `need` only grows by adding more, so
+        // the best comes from a single step over the line
+        if let Some(under) = first_set_from(before, need.saturating_sub(size), need) {
+            let total = (under + size) as u32;
+            if self.best.is_none_or(|(best, _, _)| total < best) {
+                self.best = Some((total, idx, under));
+            }
+        }
+    }
+
+    // Each total was first reached from one that was reachable before the
+    // deleted directory's subtree was visited, so the chain never nests
+    fn reconstruct_under(&self, mut total: usize, chosen: &mut Vec<usize>) {
+        while total > 0 {
+            let idx = self.first_by[total] as usize;
+            chosen.push(idx);
+            total -= node_size(self.tree.get_node(idx)) as usize;
+        }
+    }
+
+    fn plan(&self) -> Option<Vec<usize>> {
+        if self.need == 0 {
+            return Some(vec![]);
+        }
+        let (_, node, under) = self.best?;
+        let mut chosen = vec![node];
+        self.reconstruct_under(under, &mut chosen);
+        chosen.sort();
+        Some(chosen)
+    }
+}
+
+fn need_to_delete(tree: &ArenaTree<ElfFile>, total_space: u32, needed_size: u32) -> u32 {
+    let total_used = node_size(tree.get_node(0));
+    let available = total_space
+        .checked_sub(needed_size)
+        .expect("Required free space is bigger than the disk");
+    total_used.saturating_sub(available)
+}
+
+fn parse_size_arg(arg: Option<String>) -> u32 {
+    arg.and_then(|size| size.parse().ok())
+        .expect("Sizes should be non-negative integers")
+}
+
+fn print_plan(tree: &ArenaTree<ElfFile>, need: u32) {
+    println!("Need to free {}", need);
+    match smallest_single_dir(tree, need) {
+        Some(idx) => println!(
+            "Single directory: {} ({})",
+            tree.path_of(idx),
+            node_size(tree.get_node(idx))
+        ),
+        None => println!("Single directory: none is big enough"),
+    }
+    match DeletionPlanner::new(tree, need).plan() {
+        Some(chosen) => {
+            let total: u32 = chosen
+                .iter()
+                .map(|&idx| node_size(tree.get_node(idx)))
+                .sum();
+            println!("Best set: {} directories, {} in total", chosen.len(), total);
+            for idx in chosen {
+                println!(
+                    "  {} ({})",
+                    tree.path_of(idx),
+                    node_size(tree.get_node(idx))
+                );
+            }
+        }
+        None => println!("Best set: not enough space can be freed"),
+    }
+}
+
+fn read_tree(json_path: Option<String>) -> ArenaTree<ElfFile> {
+    match json_path {
+        Some(path) => {
+            let contents = fs::read_to_string(path).expect("JSON file not found");
+            let value = serde_json::from_str(&contents).expect("Couldn't parse JSON");
+            export::from_json(&value).unwrap_or_else(|err| panic!("Bad JSON, {}", err))
+        }
+        None => {
+            let lines = read_lines();
+            let mut tree =
+                build_tree(lines).unwrap_or_else(|err| panic!("Bad terminal output, {}", err));
+            fill_dir_sizes(&mut tree);
+            tree
+        }
     }
 }
 
 fn main() {
-    let lines = read_lines();
-    let tree = build_tree(lines);
+    let mut total_space: u32 = 70000000;
+    let mut needed_size: u32 = 30000000;
+    let mut json_path: Option<String> = None;
+    let mut args: Vec<String> = vec![];
+    let mut raw_args = env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        match arg.as_str() {
+            "--disk" => total_space = parse_size_arg(raw_args.next()),
+            "--free" => needed_size = parse_size_arg(raw_args.next()),
+            "--from-json" => json_path = Some(raw_args.next().expect("Missing JSON path")),
+            _ => args.push(arg),
+        }
+    }
+    let tree = read_tree(json_path);
+
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("export") => {
+            let json = serde_json::to_string_pretty(&export::to_json(&tree, 0)).unwrap();
+            match args.next() {
+                Some(path) => fs::write(path, json).expect("Couldn't write JSON"),
+                None => println!("{}", json),
+            }
+            return;
+        }
+        Some("materialise") => {
+            let root = export::materialise(&tree, args.next().map(PathBuf::from));
+            println!("{}", root.display());
+            return;
+        }
+        Some("plan") => {
+            let need = need_to_delete(&tree, total_space, needed_size);
+            print_plan(&tree, need);
+            return;
+        }
+        Some("du") => {
+            let path = args.next().unwrap_or_else(|| "/".to_string());
+            let root = tree
+                .lookup(&path)
+                .unwrap_or_else(|err| panic!("Bad path, {}", err));
+            du(&tree, root);
+            return;
+        }
+        Some("tree") => {
+            let (path, options) = parse_tree_options(args);
+            let root = tree
+                .lookup(&path)
+                .unwrap_or_else(|err| panic!("Bad path, {}", err));
+            let root_size = format_size(node_size(tree.get_node(root)), &options);
+            println!("{} ({})", tree.path_of(root), root_size);
+            for line in render_tree(&tree, root, &options) {
+                println!("{}", line);
+            }
+            return;
+        }
+        _ => {}
+    }
+
     let sizes = tree
         .arena
         .iter()
         .filter(|node| node.val.t == ElfType::Dir)
-        .map(|node| compute_node_size(&tree, &node));
+        .map(node_size);
     let part_a: u32 = sizes.clone().filter(|&size| size <= 100000).sum();
 
-    let total_used = compute_node_size(&tree, tree.get_node(0));
-    let total_space = 70000000;
-    let needed_size = 30000000;
-    let need_to_delete = total_used - (total_space - needed_size);
-
-    let mut sizes_vec: Vec<u32> = sizes.collect();
-    sizes_vec.sort();
-    //println!("{:#?}", sizes_vec);
-    let part_b = sizes_vec
-        .into_iter()
-        .find(|&size| size >= need_to_delete)
-        .unwrap();
+    let need_to_delete = need_to_delete(&tree, total_space, needed_size);
+    let part_b =
+        node_size(tree.get_node(
+            smallest_single_dir(&tree, need_to_delete).expect("No directory is big enough"),
+        ));
 
     println!("{}", part_a);
-    //println!("{}", need_to_delete);
     println!("{}", part_b);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The puzzle's sample, plus a directory /f of 10000 next to /a
+    const SAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+dir f
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k
+$ cd ..
+$ cd f
+$ ls
+10000 z";
+
+    fn parse_session(session: &str) -> Result<ArenaTree<ElfFile>, VfsError> {
+        let lines = session
+            .lines()
+            .map(|line| parse_line(line.split(' ').map(|s| s.to_string()).collect()))
+            .collect();
+        build_tree(lines)
+    }
+
+    fn sample_tree() -> ArenaTree<ElfFile> {
+        let mut tree = parse_session(SAMPLE).unwrap();
+        fill_dir_sizes(&mut tree);
+        tree
+    }
+
+    fn paths(tree: &ArenaTree<ElfFile>, chosen: &[usize]) -> Vec<String> {
+        chosen.iter().map(|&idx| tree.path_of(idx)).collect()
+    }
+
+    #[test]
+    fn best_set_beats_single_directory() {
+        let tree = sample_tree();
+        let need = 100000;
+        let single = smallest_single_dir(&tree, need).unwrap();
+        assert_eq!(tree.path_of(single), "/d");
+        assert_eq!(node_size(tree.get_node(single)), 24933642);
+
+        let chosen = DeletionPlanner::new(&tree, need).plan().unwrap();
+        assert_eq!(paths(&tree, &chosen), vec!["/a", "/f"]);
+    }
+
+    #[test]
+    fn best_set_can_be_a_single_directory() {
+        let tree = sample_tree();
+        let chosen = DeletionPlanner::new(&tree, 24000000).plan().unwrap();
+        assert_eq!(paths(&tree, &chosen), vec!["/d"]);
+    }
+
+    #[test]
+    fn best_set_needs_enough_space() {
+        let tree = sample_tree();
+        let total = node_size(tree.get_node(0));
+        assert!(DeletionPlanner::new(&tree, total + 1).plan().is_none());
+        assert_eq!(DeletionPlanner::new(&tree, 0).plan(), Some(vec![]));
+    }
+
+    #[test]
+    fn json_round_trip_rejects_repeated_names() {
+        let tree = sample_tree();
+        let json = export::to_json(&tree, 0);
+        let imported = export::from_json(&json).unwrap();
+        assert_eq!(export::to_json(&imported, 0), json);
+
+        let repeated = serde_json::json!({
+            "name": "/",
+            "type": "dir",
+            "children": [
+                {"name": "a", "type": "dir", "children": []},
+                {"name": "a", "type": "dir", "children": []},
+            ],
+        });
+        assert!(matches!(
+            export::from_json(&repeated),
+            Err(VfsError::BadJson(_))
+        ));
+    }
+
+    #[test]
+    fn listing_rejects_names_that_leave_the_directory() {
+        for name in ["..", ".", "a/b"] {
+            let session = format!("$ cd /\n$ ls\ndir {}", name);
+            assert!(matches!(parse_session(&session), Err(VfsError::BadName(_))));
+            let session = format!("$ cd /\n$ ls\n123 {}", name);
+            assert!(matches!(parse_session(&session), Err(VfsError::BadName(_))));
+        }
+    }
+}
@@ -1,154 +1,374 @@
-use itertools::Itertools;
+mod render;
+
+use std::env;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::path::Path;
 
-struct Accumulator {
-    max_height: i32,
-    visible_trees: Vec<(usize, usize)>,
+// Grid dimensions; positions are always (row, col)
+#[derive(Debug, Clone, Copy)]
+struct Dims {
+    rows: usize,
+    cols: usize,
 }
 
-impl Default for Accumulator {
-    fn default() -> Self {
-        Self {
-            max_height: -1,
-            visible_trees: vec![],
-        }
+fn dims<T>(board: &[Vec<T>]) -> Dims {
+    Dims {
+        rows: board.len(),
+        cols: board.first().map_or(0, |row| row.len()),
     }
 }
 
 fn read_lines() -> Vec<Vec<i32>> {
     let file = File::open("./data/input").unwrap();
     let lines = io::BufReader::new(file).lines();
-    lines
-        .map(|line| line.unwrap())
+    parse_board(lines.map(|line| line.unwrap()))
+}
+
+fn parse_board(lines: impl Iterator<Item = String>) -> Vec<Vec<i32>> {
+    let board: Vec<Vec<i32>> = lines
+        .filter(|line| !line.trim().is_empty())
         .map(|line| {
             line.trim()
                 .chars()
-                .map(|c| i32::try_from(c.to_digit(10).unwrap()).unwrap())
+                .map(|c| i32::try_from(c.to_digit(10).expect("Heights should be digits")).unwrap())
                 .collect()
         })
+        .collect();
+    let cols = dims(&board).cols;
+    if let Some(row_index) = board.iter().position(|row| row.len() != cols) {
+        panic!(
+            "Row {} has {} trees but row 1 has {}",
+            row_index + 1,
+            board[row_index].len(),
+            cols
+        );
+    }
+    board
+}
+
+// Directions a tree can look in, as (row, col) steps
+const ORTHOGONAL: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+const DIAGONAL: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+// When a tree in the line of sight stops the view
+#[derive(Debug, Clone, Copy)]
+enum Blocking {
+    StrictlyTaller,
+    TallerOrEqual,
+    // Anything at least (house height - tolerance) blocks
+    Tolerance(i32),
+}
+
+impl Blocking {
+    fn blocks(&self, blocker: i32, house_height: i32) -> bool {
+        match self {
+            Blocking::StrictlyTaller => blocker > house_height,
+            Blocking::TallerOrEqual => blocker >= house_height,
+            Blocking::Tolerance(tolerance) => blocker >= house_height - tolerance,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Combine {
+    Product,
+    Sum,
+    Min,
+}
+
+struct SightModel {
+    directions: Vec<(i32, i32)>,
+    blocking: Blocking,
+    combine: Combine,
+}
+
+impl Default for SightModel {
+    fn default() -> Self {
+        Self {
+            directions: ORTHOGONAL.to_vec(),
+            blocking: Blocking::TallerOrEqual,
+            combine: Combine::Product,
+        }
+    }
+}
+
+// Per-tree results for the whole grid
+struct ForestAnalysis {
+    // Whether the tree can be seen from at least one edge
+    visible: Vec<Vec<bool>>,
+    // View distance looking along each of the model's directions, in order
+    view_distances: Vec<Vec<Vec<usize>>>,
+    scenic_scores: Vec<Vec<usize>>,
+}
+
+fn step(pos: (usize, usize), direction: (i32, i32), dims: Dims) -> Option<(usize, usize)> {
+    let row = pos.0.checked_add_signed(direction.0 as isize)?;
+    let col = pos.1.checked_add_signed(direction.1 as isize)?;
+    if row < dims.rows && col < dims.cols {
+        Some((row, col))
+    } else {
+        None
+    }
+}
+
+// The lines of trees to walk so that each tree looks back towards the start.
+// Each line starts at a tree whose view in `direction` leaves the grid at once
+fn sweep_lines(dims: Dims, direction: (i32, i32)) -> Vec<Vec<(usize, usize)>> {
+    let backwards = (-direction.0, -direction.1);
+    (0..dims.rows)
+        .flat_map(|row| (0..dims.cols).map(move |col| (row, col)))
+        .filter(|&pos| step(pos, direction, dims).is_none())
+        .map(|start| {
+            let mut line = vec![start];
+            while let Some(next) = step(*line.last().unwrap(), backwards, dims) {
+                line.push(next);
+            }
+            line
+        })
         .collect()
 }
 
-// Returns indicies of visible trees from this direction
-fn get_visible_from_iter<'a>(
-    heights: impl Iterator<Item = ((usize, usize), &'a i32)>,
-) -> Vec<(usize, usize)> {
-    heights
-        .fold(Default::default(), |accum: Accumulator, (index, height)| {
-            if *height > accum.max_height {
-                let mut visible_trees = accum.visible_trees;
-                visible_trees.push(index);
-                Accumulator {
-                    max_height: height.clone(),
-                    visible_trees,
+// Keeps a stack of the trees not yet hidden behind a later tree that would
+// block anything they block. After popping everything that can't block the
+// current tree, the top of the stack is what stops its view; an empty stack
+// means it sees the edge. This relies on every blocking rule letting a
+// tree block whatever a shorter tree behind it would
+fn sweep_line(
+    board: &[Vec<i32>],
+    line: &[(usize, usize)],
+    d: usize,
+    blocking: Blocking,
+    analysis: &mut ForestAnalysis,
+) {
+    let mut stack: Vec<(usize, i32)> = vec![];
+    for (step, &(row, col)) in line.iter().enumerate() {
+        let height = board[row][col];
+        while stack
+            .last()
+            .is_some_and(|&(_, blocker)| !blocking.blocks(blocker, height))
+        {
+            stack.pop();
+        }
+        let distance = match stack.last() {
+            Some(&(blocker_step, _)) => step - blocker_step,
+            None => {
+                analysis.visible[row][col] = true;
+                step
+            }
+        };
+        analysis.view_distances[row][col][d] = distance;
+        stack.push((step, height));
+    }
+}
+
+fn analyse_forest(board: &[Vec<i32>], model: &SightModel) -> ForestAnalysis {
+    let dims = dims(board);
+    let n_directions = model.directions.len();
+    let mut analysis = ForestAnalysis {
+        visible: vec![vec![false; dims.cols]; dims.rows],
+        view_distances: vec![vec![vec![0; n_directions]; dims.cols]; dims.rows],
+        scenic_scores: vec![vec![0; dims.cols]; dims.rows],
+    };
+    for (d, &direction) in model.directions.iter().enumerate() {
+        for line in sweep_lines(dims, direction) {
+            sweep_line(board, &line, d, model.blocking, &mut analysis);
+        }
+    }
+    for row in 0..dims.rows {
+        for col in 0..dims.cols {
+            let distances = analysis.view_distances[row][col].iter().copied();
+            analysis.scenic_scores[row][col] = match model.combine {
+                Combine::Product => distances.product(),
+                Combine::Sum => distances.sum(),
+                Combine::Min => distances.min().unwrap_or(0),
+            };
+        }
+    }
+    analysis
+}
+
+fn parse_model(args: &mut Vec<String>) -> SightModel {
+    let mut model = SightModel::default();
+    let mut remaining = vec![];
+    let mut args_iter = args.drain(..);
+    while let Some(arg) = args_iter.next() {
+        let mut value = || args_iter.next().unwrap_or_default();
+        match arg.as_str() {
+            "--directions" => {
+                model.directions = match value().as_str() {
+                    "4" => ORTHOGONAL.to_vec(),
+                    "8" => ORTHOGONAL.iter().chain(DIAGONAL.iter()).copied().collect(),
+                    _ => panic!("--directions should be 4 or 8"),
                 }
-            } else {
-                accum
             }
-        })
-        .visible_trees
-}
-
-fn get_num_visible(board: &Vec<Vec<i32>>) -> usize {
-    let mut visible_trees: Vec<(usize, usize)> = vec![];
-    // Add trees visible in each row
-    for (row_index, row) in board.iter().enumerate() {
-        let indexed_iterator = row
-            .iter()
-            .enumerate()
-            .map(|(col_index, height)| ((row_index, col_index), height));
-        let mut visible_from_left = get_visible_from_iter(indexed_iterator.clone());
-        let mut visible_from_right = get_visible_from_iter(indexed_iterator.rev());
-        visible_trees.append(&mut visible_from_left);
-        visible_trees.append(&mut visible_from_right);
-    }
-    // Add trees visible in each column
-    let row_size = board[0].len();
-    for col_index in 0..row_size {
-        let indexed_iterator = board
-            .iter()
-            .enumerate()
-            .map(|(row_index, row)| ((row_index, col_index), &row[col_index]));
-        let mut visible_from_top = get_visible_from_iter(indexed_iterator.clone());
-        let mut visible_from_bottom = get_visible_from_iter(indexed_iterator.rev());
-        visible_trees.append(&mut visible_from_top);
-        visible_trees.append(&mut visible_from_bottom);
-    }
-    visible_trees.into_iter().unique().count()
-}
-
-fn compute_view_distance<'a>(iterator: impl Iterator<Item = &'a i32>, house_height: i32) -> usize {
-    let as_vec: Vec<&i32> = iterator.collect();
-    let size = as_vec.len();
-    let first_position = as_vec
-        .into_iter()
-        .position(|height| *height >= house_height);
-    // If first_position was 0 then we can see 1 tree
-    match first_position {
-        Some(first_position) => first_position + 1,
-        None => size,
-    }
-}
-
-fn on_edge(pos: (usize, usize), mat_size: (usize, usize)) -> bool {
-    pos.0 == 0 || pos.1 == 0 || pos.0 == mat_size.0 - 1 || pos.1 == mat_size.1 - 1
-}
-
-fn compute_scenic_score(board: &Vec<Vec<i32>>, pos: (usize, usize)) -> usize {
-    let col_size = board.len();
-    let row_size = board[0].len();
-    let base_row = pos.0;
-    let base_col = pos.1;
-    let house_height = board[base_row][base_col];
-
-    // Deal with edge cases first (where score is necessarily 0
-    if on_edge(pos, (row_size, col_size)) {
-        return 0;
-    }
-    // Compute view distance in each direction
-    // Not on edge means these iteators will always be non-empty
-    let left_score = compute_view_distance(
-        (0..base_col)
-            .rev()
-            .map(|col_index| &board[base_row][col_index]),
-        house_height,
-    );
-    let right_score = compute_view_distance(
-        (base_col + 1..col_size).map(|col| &board[base_row][col]),
-        house_height,
-    );
-    let up_score = compute_view_distance(
-        (0..base_row)
-            .rev()
-            .map(|row_index| &board[row_index][base_col]),
-        house_height,
-    );
-    let down_score = compute_view_distance(
-        (base_row + 1..row_size).map(|row| &board[row][base_col]),
-        house_height,
-    );
-    // Combine to scenic score
-    left_score * right_score * up_score * down_score
-}
-
-fn compute_max_score(board: &Vec<Vec<i32>>) -> usize {
-    let col_size = board.len();
-    let row_size = board[0].len();
-    // Iterator over all matrix positions
-    let index_iteator = (0..row_size)
-        .map(|row| (0..col_size).map(move |col| (row, col)))
-        .flatten();
-    index_iteator
-        .map(|pos| compute_scenic_score(board, pos))
+            "--block" => {
+                let rule = value();
+                model.blocking = match rule.as_str() {
+                    "taller" => Blocking::StrictlyTaller,
+                    "taller-or-equal" => Blocking::TallerOrEqual,
+                    other => Blocking::Tolerance(
+                        other
+                            .strip_prefix("tolerance:")
+                            .and_then(|tolerance| tolerance.parse().ok())
+                            .filter(|&tolerance: &i32| tolerance >= 0)
+                            .expect("--block should be taller, taller-or-equal or tolerance:N"),
+                    ),
+                }
+            }
+            "--combine" => {
+                model.combine = match value().as_str() {
+                    "product" => Combine::Product,
+                    "sum" => Combine::Sum,
+                    "min" => Combine::Min,
+                    _ => panic!("--combine should be product, sum or min"),
+                }
+            }
+            _ => remaining.push(arg),
+        }
+    }
+    drop(args_iter);
+    *args = remaining;
+    model
+}
+
+fn get_num_visible(analysis: &ForestAnalysis) -> usize {
+    analysis
+        .visible
+        .iter()
+        .flatten()
+        .filter(|&&visible| visible)
+        .count()
+}
+
+fn compute_max_score(analysis: &ForestAnalysis) -> usize {
+    analysis
+        .scenic_scores
+        .iter()
+        .flatten()
+        .copied()
         .max()
-        .unwrap()
+        .unwrap_or(0)
+}
+
+// ANSI output is one character per tree, so only small grids fit a terminal
+const MAX_ANSI_COLS: usize = 120;
+
+fn show(board: &[Vec<i32>], analysis: &ForestAnalysis, layer: &str) {
+    if dims(board).cols > MAX_ANSI_COLS {
+        println!("Grid is too wide to show, export it as images instead");
+        return;
+    }
+    let mut labels: Vec<Vec<char>> = board
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&height| char::from_digit(height as u32, 10).unwrap())
+                .collect()
+        })
+        .collect();
+    let picture = match layer {
+        "heights" => render::heights_picture(board),
+        "visible" => render::visible_picture(analysis),
+        "scenic" => {
+            if let Some((row, col)) = render::best_tree(analysis) {
+                labels[row][col] = '*';
+            }
+            render::scenic_picture(analysis)
+        }
+        _ => panic!("Layer should be heights, visible or scenic"),
+    };
+    print!("{}", picture.to_ansi(Some(&labels)));
 }
 
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let model = parse_model(&mut args);
     let board = read_lines();
-    let visible = get_num_visible(&board);
+    let analysis = analyse_forest(&board, &model);
+
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("export") => {
+            let dir = args.next().expect("Missing output directory");
+            let scale = args.next().map_or(4, |scale| {
+                scale.parse().expect("Scale should be a positive integer")
+            });
+            render::export_all(&board, &analysis, Path::new(&dir), scale.max(1));
+            return;
+        }
+        Some("show") => {
+            show(
+                &board,
+                &analysis,
+                &args.next().unwrap_or_else(|| "scenic".to_string()),
+            );
+            return;
+        }
+        _ => {}
+    }
+
+    let visible = get_num_visible(&analysis);
     println!("{}", visible);
-    let max_score = compute_max_score(&board);
+    let max_score = compute_max_score(&analysis);
     println!("{}", max_score);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(rows: &[&str]) -> Vec<Vec<i32>> {
+        parse_board(rows.iter().map(|row| row.to_string()))
+    }
+
+    // Number of visible trees and the best scenic score, with the puzzle's rules
+    fn answers(rows: &[&str]) -> (usize, usize) {
+        let analysis = analyse_forest(&board(rows), &SightModel::default());
+        (get_num_visible(&analysis), compute_max_score(&analysis))
+    }
+
+    #[test]
+    fn sample() {
+        assert_eq!(
+            answers(&["30373", "25512", "65332", "33549", "35390"]),
+            (21, 8)
+        );
+    }
+
+    #[test]
+    fn tall_grid() {
+        // The 14 edge trees plus the 5 and the 2. The 5 sees 1 left, 1 right,
+        // 2 up and 3 down
+        assert_eq!(
+            answers(&["393", "111", "151", "111", "121", "393"]),
+            (16, 6)
+        );
+    }
+
+    #[test]
+    fn wide_grid() {
+        assert_eq!(answers(&["311113", "915129", "311113"]), (16, 6));
+    }
+
+    #[test]
+    fn two_rows() {
+        assert_eq!(answers(&["123456", "654321"]), (12, 0));
+    }
+
+    #[test]
+    fn single_row() {
+        assert_eq!(answers(&["30373"]), (5, 0));
+    }
+
+    #[test]
+    fn single_column() {
+        assert_eq!(answers(&["3", "0", "3", "7", "3"]), (5, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Row 2 has 2 trees but row 1 has 3")]
+    fn ragged_rows() {
+        board(&["123", "12", "123"]);
+    }
+}
@@ -0,0 +1,177 @@
+use super::{dims, Dims, ForestAnalysis};
+use std::fs;
+use std::path::Path;
+
+type Rgb = [u8; 3];
+
+const BEST_MARKER: Rgb = [255, 0, 0];
+
+// Dark blue through teal and green to yellow, roughly viridis
+const RAMP: [Rgb; 5] = [
+    [68, 1, 84],
+    [59, 82, 139],
+    [33, 145, 140],
+    [94, 201, 98],
+    [253, 231, 37],
+];
+
+// Maps t in [0, 1] onto the ramp, interpolating between stops
+fn ramp_colour(t: f64) -> Rgb {
+    let scaled = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64;
+    let lower = (scaled.floor() as usize).min(RAMP.len() - 2);
+    let frac = scaled - lower as f64;
+    let mut colour = [0; 3];
+    for channel in 0..3 {
+        let from = RAMP[lower][channel] as f64;
+        let to = RAMP[lower + 1][channel] as f64;
+        colour[channel] = (from + (to - from) * frac).round() as u8;
+    }
+    colour
+}
+
+fn grey(level: u8) -> Rgb {
+    [level, level, level]
+}
+
+// A colour for every tree, in row-major order
+pub struct Picture {
+    dims: Dims,
+    pixels: Vec<Vec<Rgb>>,
+}
+
+pub fn best_tree(analysis: &ForestAnalysis) -> Option<(usize, usize)> {
+    let mut best: Option<((usize, usize), usize)> = None;
+    for (row, scores) in analysis.scenic_scores.iter().enumerate() {
+        for (col, &score) in scores.iter().enumerate() {
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some(((row, col), score));
+            }
+        }
+    }
+    best.map(|(pos, _)| pos)
+}
+
+pub fn heights_picture(board: &[Vec<i32>]) -> Picture {
+    Picture {
+        dims: dims(board),
+        pixels: board
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&height| grey((height * 255 / 9) as u8))
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+pub fn visible_picture(analysis: &ForestAnalysis) -> Picture {
+    Picture {
+        dims: dims(&analysis.visible),
+        pixels: analysis
+            .visible
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&visible| grey(if visible { 255 } else { 0 }))
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+// Scores are spread over the ramp by square root, since a few trees dwarf the rest
+pub fn scenic_picture(analysis: &ForestAnalysis) -> Picture {
+    let max_score = analysis
+        .scenic_scores
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let mut pixels: Vec<Vec<Rgb>> = analysis
+        .scenic_scores
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&score| ramp_colour((score as f64 / max_score as f64).sqrt()))
+                .collect()
+        })
+        .collect();
+    if let Some((row, col)) = best_tree(analysis) {
+        pixels[row][col] = BEST_MARKER;
+    }
+    Picture {
+        dims: dims(&analysis.scenic_scores),
+        pixels,
+    }
+}
+
+impl Picture {
+    fn is_grey(&self) -> bool {
+        self.pixels
+            .iter()
+            .flatten()
+            .all(|&[r, g, b]| r == g && g == b)
+    }
+
+    // Binary PGM if every pixel is grey, otherwise binary PPM. Each tree
+    // becomes a scale x scale block
+    pub fn to_netpbm(&self, scale: usize) -> Vec<u8> {
+        let grey_only = self.is_grey();
+        let magic = if grey_only { "P5" } else { "P6" };
+        let mut bytes = format!(
+            "{}\n{} {}\n255\n",
+            magic,
+            self.dims.cols * scale,
+            self.dims.rows * scale
+        )
+        .into_bytes();
+        for row in self.pixels.iter() {
+            for _ in 0..scale {
+                for pixel in row.iter() {
+                    for _ in 0..scale {
+                        if grey_only {
+                            bytes.push(pixel[0]);
+                        } else {
+                            bytes.extend_from_slice(pixel);
+                        }
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    // Each tree as a coloured cell, labelled with `labels` when given
+    pub fn to_ansi(&self, labels: Option<&[Vec<char>]>) -> String {
+        let mut out = String::new();
+        for (row, pixels) in self.pixels.iter().enumerate() {
+            for (col, &[r, g, b]) in pixels.iter().enumerate() {
+                let label = labels.map_or(' ', |labels| labels[row][col]);
+                // Dark text on light cells and vice versa
+                let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+                let fg = if luma > 128.0 { 0 } else { 255 };
+                out.push_str(&format!(
+                    "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m{}",
+                    r, g, b, fg, fg, fg, label
+                ));
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
+    }
+}
+
+pub fn export_all(board: &[Vec<i32>], analysis: &ForestAnalysis, dir: &Path, scale: usize) {
+    fs::create_dir_all(dir).expect("Couldn't create output directory");
+    let pictures = [
+        ("heights.pgm", heights_picture(board)),
+        ("visible.pgm", visible_picture(analysis)),
+        ("scenic.ppm", scenic_picture(analysis)),
+    ];
+    for (name, picture) in pictures {
+        fs::write(dir.join(name), picture.to_netpbm(scale)).expect("Couldn't write image");
+    }
+}
@@ -1,6 +1,10 @@
-use itertools::Itertools;
+mod render;
+
+use std::collections::HashSet;
+use std::env;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::path::Path;
 
 #[derive(Clone, Copy, Debug)]
 enum Move {
@@ -8,38 +12,74 @@ enum Move {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+// How a knot responds when the knot in front of it moves
+#[derive(Clone, Copy, Debug)]
+enum FollowRule {
+    // Stay put while within k cells (Chebyshev), otherwise step one cell towards the leader
+    Slack(i32),
+    // When no longer touching, jump to where the leader just was
+    JumpToPrevious,
+}
+
+impl FollowRule {
+    fn parse(rule: &str) -> FollowRule {
+        match rule {
+            "jump" => FollowRule::JumpToPrevious,
+            other => FollowRule::Slack(
+                other
+                    .strip_prefix("slack:")
+                    .and_then(|slack| slack.parse().ok())
+                    .filter(|&slack| slack >= 1)
+                    .expect("Follow rule should be slack:K or jump"),
+            ),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 struct KnotState {
-    head_pos: (i32, i32),
-    tail_pos: (i32, i32),
-    tail_history: Vec<(i32, i32)>,
+    pos: (i32, i32),
+    // Every cell this knot has visited, if we are tracking it
+    visited: Option<HashSet<(i32, i32)>>,
 }
 
+// Knot 0 is the head and the last knot is the tail
 #[derive(Clone, Debug)]
 struct KnotChain {
-    length: usize,
     knot_states: Vec<KnotState>,
+    rule: FollowRule,
 }
 
-impl Default for KnotState {
-    fn default() -> Self {
+impl KnotState {
+    fn new(tracked: bool) -> Self {
         Self {
-            head_pos: (0, 0),
-            tail_pos: (0, 0),
-            tail_history: vec![(0, 0)],
+            pos: (0, 0),
+            visited: tracked.then(|| HashSet::from([(0, 0)])),
         }
     }
 }
 
 impl KnotChain {
-    fn new(length: usize) -> Self {
+    // The tail's visited cells are always tracked, the other knots' only if asked
+    fn new(n_knots: usize, track_all: bool, rule: FollowRule) -> Self {
+        assert!(n_knots >= 1, "A rope needs at least one knot");
         Self {
-            length,
-            knot_states: vec![KnotState::default(); length],
+            knot_states: (0..n_knots)
+                .map(|idx| KnotState::new(track_all || idx == n_knots - 1))
+                .collect(),
+            rule,
         }
     }
+
+    fn tail(&self) -> &KnotState {
+        self.knot_states.last().unwrap()
+    }
 }
 
 fn move_parser(move_str: &str) -> Move {
@@ -48,6 +88,10 @@ fn move_parser(move_str: &str) -> Move {
         "D" => Move::Down,
         "L" => Move::Left,
         "R" => Move::Right,
+        "UL" => Move::UpLeft,
+        "UR" => Move::UpRight,
+        "DL" => Move::DownLeft,
+        "DR" => Move::DownRight,
         _ => panic!("Invalid move"),
     }
 }
@@ -58,17 +102,22 @@ fn parse_line(line: Vec<&str>) -> Vec<Move> {
     vec![head_move; count]
 }
 
-fn read_lines() -> impl Iterator<Item = Move> {
+// Each line of input along with the single steps it expands to
+fn read_instructions() -> Vec<(String, Vec<Move>)> {
     let file = File::open("./data/input").expect("Input file not found");
     let lines = io::BufReader::new(file).lines();
     lines
         .map(|line| line.expect("Couldn't read line of input"))
         .map(|line| {
-            let split_line = line.split(" ").collect();
-            let move_vec = parse_line(split_line);
-            return move_vec.into_iter();
+            let split_line = line.split(' ').collect();
+            let moves = parse_line(split_line);
+            (line, moves)
         })
-        .flatten()
+        .collect()
+}
+
+fn read_lines() -> impl Iterator<Item = Move> {
+    read_instructions().into_iter().flat_map(|(_, moves)| moves)
 }
 
 fn get_sign(input: i32) -> i32 {
@@ -81,85 +130,249 @@ fn get_sign(input: i32) -> i32 {
     }
 }
 
-fn get_move(discrep: (i32, i32)) -> (i32, i32) {
-    let has_horiz_discrep = discrep.0 != 0;
-    let has_vert_discrep = discrep.1 != 0;
-    let horiz_differ_by_1 = discrep.0.abs() == 1;
-    let vert_differ_by_1 = discrep.1.abs() == 1;
-    // Tail should stay fixed in these scenarrios
-    let overlapping = !has_horiz_discrep && !has_vert_discrep;
-    let touching_laterally =
-        (!has_horiz_discrep && vert_differ_by_1) || (!has_vert_discrep && horiz_differ_by_1);
-    let touching_diagonally = horiz_differ_by_1 && vert_differ_by_1;
-    if overlapping || touching_laterally || touching_diagonally {
+fn chebyshev(discrep: (i32, i32)) -> i32 {
+    discrep.0.abs().max(discrep.1.abs())
+}
+
+fn get_move(discrep: (i32, i32), slack: i32) -> (i32, i32) {
+    // Tail should stay fixed while it is close enough
+    if chebyshev(discrep) <= slack {
         return (0, 0);
     };
     // We need to make a move, moving by 1 in each of the directions with discrep
     (get_sign(discrep.0), get_sign(discrep.1))
 }
 
-fn update_head(state: &mut KnotState, head_move: &Move) {
-    match head_move {
-        Move::Up => state.head_pos.0 += 1,
-        Move::Down => state.head_pos.0 -= 1,
-        Move::Left => state.head_pos.1 -= 1,
-        Move::Right => state.head_pos.1 += 1,
-    }
+fn update_head(pos: &mut (i32, i32), head_move: &Move) {
+    let (row_step, col_step) = match head_move {
+        Move::Up => (1, 0),
+        Move::Down => (-1, 0),
+        Move::Left => (0, -1),
+        Move::Right => (0, 1),
+        Move::UpLeft => (1, -1),
+        Move::UpRight => (1, 1),
+        Move::DownLeft => (-1, -1),
+        Move::DownRight => (-1, 1),
+    };
+    pos.0 += row_step;
+    pos.1 += col_step;
 }
 
-fn update_tail(state: &mut KnotState) {
-    let discrep = (
-        state.head_pos.0 - state.tail_pos.0,
-        state.head_pos.1 - state.tail_pos.1,
-    );
-    let tail_move = get_move(discrep);
-    state.tail_pos.0 += tail_move.0;
-    state.tail_pos.1 += tail_move.1;
+fn follow(
+    rule: FollowRule,
+    leader_before: (i32, i32),
+    leader: (i32, i32),
+    follower: &mut (i32, i32),
+) {
+    let discrep = (leader.0 - follower.0, leader.1 - follower.1);
+    match rule {
+        FollowRule::Slack(slack) => {
+            let tail_move = get_move(discrep, slack);
+            follower.0 += tail_move.0;
+            follower.1 += tail_move.1;
+        }
+        FollowRule::JumpToPrevious => {
+            if chebyshev(discrep) > 1 {
+                *follower = leader_before;
+            }
+        }
+    }
 }
 
 fn update_history(state: &mut KnotState) {
-    state.tail_history.push(state.tail_pos.clone());
-}
-
-fn update_state(mut state: KnotState, head_move: &Move) -> KnotState {
-    update_head(&mut state, head_move);
-    update_tail(&mut state);
-    update_history(&mut state);
-    state
+    if let Some(visited) = state.visited.as_mut() {
+        visited.insert(state.pos);
+    }
 }
 
 fn update_knot_chain(mut current_chain: KnotChain, head_move: &Move) -> KnotChain {
     // First we move the head
-    update_head(&mut current_chain.knot_states[0], head_move);
-    update_tail(&mut current_chain.knot_states[0]);
-    // Now we pass the tail of i-1 to the head of i and update the tail of i
-    for i in 1..current_chain.length {
-        current_chain.knot_states[i].head_pos = current_chain.knot_states[i - 1].tail_pos;
-        update_tail(&mut current_chain.knot_states[i]);
-    }
-    // Only update the history of the last knot
-    update_history(&mut current_chain.knot_states[current_chain.length - 1]);
+    let mut leader_before = current_chain.knot_states[0].pos;
+    update_head(&mut current_chain.knot_states[0].pos, head_move);
+    update_history(&mut current_chain.knot_states[0]);
+    // Now each knot follows the one in front of it
+    for i in 1..current_chain.knot_states.len() {
+        let leader = current_chain.knot_states[i - 1].pos;
+        let before = current_chain.knot_states[i].pos;
+        follow(
+            current_chain.rule,
+            leader_before,
+            leader,
+            &mut current_chain.knot_states[i].pos,
+        );
+        update_history(&mut current_chain.knot_states[i]);
+        leader_before = before;
+    }
     current_chain
 }
 
-fn main() {
-    let moves: Vec<Move> = read_lines().collect();
+fn simulate(moves: &[Move], n_knots: usize, track_all: bool, rule: FollowRule) -> KnotChain {
+    moves
+        .iter()
+        .fold(KnotChain::new(n_knots, track_all, rule), update_knot_chain)
+}
+
+fn positions(chain: &KnotChain) -> Vec<render::Pos> {
+    chain.knot_states.iter().map(|state| state.pos).collect()
+}
 
-    let final_state_a = moves.iter().fold(KnotState::default(), update_state);
-    let part_a = final_state_a.tail_history.iter().unique().count();
-    println!("{}", part_a);
+// Knot positions at the start and after every single step
+fn record_frames(moves: &[Move], n_knots: usize, rule: FollowRule) -> Vec<Vec<render::Pos>> {
+    let mut chain = KnotChain::new(n_knots, false, rule);
+    let mut frames = vec![positions(&chain)];
+    for head_move in moves {
+        chain = update_knot_chain(chain, head_move);
+        frames.push(positions(&chain));
+    }
+    frames
+}
 
-    let n_knots = 9;
-    let final_state_b = moves
-        .iter()
-        .fold(KnotChain::new(n_knots), update_knot_chain);
-    let part_b = final_state_b
-        .knot_states
-        .last()
-        .expect("No last knot")
-        .tail_history
+fn show(n_knots: usize, rule: FollowRule) {
+    let instructions = read_instructions();
+    let moves: Vec<Move> = instructions
         .iter()
-        .unique()
-        .count();
-    println!("{}", part_b);
+        .flat_map(|(_, moves)| moves.iter().copied())
+        .collect();
+    let frames = record_frames(&moves, n_knots, rule);
+    let bounds = render::Bounds::covering(frames.iter().flatten());
+    println!(
+        "== Initial State ==\n\n{}\n",
+        render::ascii_frame(&frames[0], &bounds)
+    );
+    let mut step = 0;
+    for (line, moves) in instructions.iter() {
+        step += moves.len();
+        println!(
+            "== {} ==\n\n{}\n",
+            line,
+            render::ascii_frame(&frames[step], &bounds)
+        );
+    }
+    let visited: HashSet<render::Pos> = frames.iter().map(|knots| *knots.last().unwrap()).collect();
+    println!(
+        "== Visited ==\n\n{}",
+        render::ascii_visited(&visited, &bounds)
+    );
+}
+
+struct ExportOptions {
+    n_knots: usize,
+    scale: usize,
+    every: usize,
+    delay: u16,
+}
+
+fn parse_export_options(args: impl Iterator<Item = String>) -> ExportOptions {
+    let mut options = ExportOptions {
+        n_knots: 10,
+        scale: 4,
+        every: 1,
+        delay: 5,
+    };
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|&value| value > 0)
+                .unwrap_or_else(|| panic!("{} should be followed by a positive integer", arg))
+        };
+        match arg.as_str() {
+            "--scale" => options.scale = value(),
+            "--every" => options.every = value(),
+            "--delay" => options.delay = value() as u16,
+            count => {
+                options.n_knots = count
+                    .parse()
+                    .expect("Knot count should be a positive integer")
+            }
+        }
+    }
+    options
+}
+
+fn export(target: &str, options: ExportOptions, as_gif: bool, rule: FollowRule) {
+    let moves: Vec<Move> = read_lines().collect();
+    let frames = record_frames(&moves, options.n_knots, rule);
+    let bounds = render::Bounds::covering(frames.iter().flatten());
+    let path = Path::new(target);
+    if as_gif {
+        render::write_gif(
+            &frames,
+            &bounds,
+            path,
+            options.scale,
+            options.every,
+            options.delay,
+        );
+    } else {
+        render::write_ppm_frames(&frames, &bounds, path, options.scale, options.every);
+    }
+}
+
+fn visited_count(state: &KnotState) -> usize {
+    state.visited.as_ref().map_or(0, |visited| visited.len())
+}
+
+fn main() {
+    // The follow rule applies to every mode, so pull it out first
+    let mut rule = FollowRule::Slack(1);
+    let mut other_args: Vec<String> = vec![];
+    let mut raw_args = env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        if arg == "--rule" {
+            rule = FollowRule::parse(&raw_args.next().unwrap_or_default());
+        } else {
+            other_args.push(arg);
+        }
+    }
+
+    let mut args = other_args.into_iter().peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("show") => {
+            args.next();
+            let n_knots = args.next().map_or(10, |count| {
+                count
+                    .parse()
+                    .expect("Knot count should be a positive integer")
+            });
+            show(n_knots, rule);
+            return;
+        }
+        Some("frames") | Some("gif") => {
+            let as_gif = args.next().as_deref() == Some("gif");
+            let target = args.next().expect("Missing output path");
+            export(&target, parse_export_options(args), as_gif, rule);
+            return;
+        }
+        _ => {}
+    }
+
+    let mut track_all = false;
+    let mut knot_counts: Vec<usize> = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--all-knots" => track_all = true,
+            count => knot_counts.push(
+                count
+                    .parse()
+                    .expect("Knot count should be a positive integer"),
+            ),
+        }
+    }
+    // Part A is a rope of 2 knots, part B one of 10
+    if knot_counts.is_empty() {
+        knot_counts = vec![2, 10];
+    }
+
+    let moves: Vec<Move> = read_lines().collect();
+    for n_knots in knot_counts {
+        let final_chain = simulate(&moves, n_knots, track_all, rule);
+        println!("{}", visited_count(final_chain.tail()));
+        if track_all {
+            for (idx, state) in final_chain.knot_states.iter().enumerate() {
+                println!("  knot {}: {}", idx, visited_count(state));
+            }
+        }
+    }
 }
@@ -0,0 +1,224 @@
+use gif::{Encoder, Frame, Repeat};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::Path;
+
+// Positions are (row, col) with rows increasing upwards, as in the simulation
+pub type Pos = (i32, i32);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    min: Pos,
+    max: Pos,
+}
+
+impl Bounds {
+    // Smallest box holding every position, always including the start
+    pub fn covering<'a>(positions: impl Iterator<Item = &'a Pos>) -> Bounds {
+        positions.fold(
+            Bounds {
+                min: (0, 0),
+                max: (0, 0),
+            },
+            |bounds, &(row, col)| Bounds {
+                min: (bounds.min.0.min(row), bounds.min.1.min(col)),
+                max: (bounds.max.0.max(row), bounds.max.1.max(col)),
+            },
+        )
+    }
+
+    fn width(&self) -> usize {
+        (self.max.1 - self.min.1 + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max.0 - self.min.0 + 1) as usize
+    }
+
+    // Cells from the top row down, left to right
+    fn cells(&self) -> impl Iterator<Item = Vec<Pos>> + '_ {
+        (self.min.0..=self.max.0)
+            .rev()
+            .map(|row| (self.min.1..=self.max.1).map(|col| (row, col)).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Empty,
+    Visited,
+    Start,
+    Head,
+    Middle(usize),
+    Tail,
+}
+
+// The puzzle labels a 2-knot rope H and T, and longer ones H, 1, 2, ...
+fn knot_cell(idx: usize, n_knots: usize) -> Cell {
+    if idx == 0 {
+        Cell::Head
+    } else if idx == n_knots - 1 && (n_knots == 2 || n_knots > 10) {
+        Cell::Tail
+    } else {
+        Cell::Middle(idx)
+    }
+}
+
+// Knots nearer the head are drawn over those behind them
+fn cell_at(pos: Pos, knots: &[Pos], visited: &HashSet<Pos>) -> Cell {
+    match knots.iter().position(|&knot| knot == pos) {
+        Some(idx) => knot_cell(idx, knots.len()),
+        None if pos == (0, 0) => Cell::Start,
+        None if visited.contains(&pos) => Cell::Visited,
+        None => Cell::Empty,
+    }
+}
+
+fn cell_char(cell: Cell) -> char {
+    match cell {
+        Cell::Empty => '.',
+        Cell::Visited => '#',
+        Cell::Start => 's',
+        Cell::Head => 'H',
+        Cell::Middle(idx) => char::from_digit((idx % 10) as u32, 10).unwrap(),
+        Cell::Tail => 'T',
+    }
+}
+
+pub fn ascii_frame(knots: &[Pos], bounds: &Bounds) -> String {
+    render_ascii(bounds, |pos| cell_at(pos, knots, &HashSet::new()))
+}
+
+pub fn ascii_visited(visited: &HashSet<Pos>, bounds: &Bounds) -> String {
+    render_ascii(bounds, |pos| cell_at(pos, &[], visited))
+}
+
+fn render_ascii(bounds: &Bounds, cell: impl Fn(Pos) -> Cell) -> String {
+    bounds
+        .cells()
+        .map(|row| row.into_iter().map(|pos| cell_char(cell(pos))).collect())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Indexed colours, shared by the PPM and GIF output
+const PALETTE: [[u8; 3]; 6] = [
+    [255, 255, 255],
+    [200, 200, 200],
+    [0, 0, 0],
+    [220, 30, 30],
+    [40, 140, 60],
+    [30, 70, 220],
+];
+
+fn palette_index(cell: Cell) -> u8 {
+    match cell {
+        Cell::Empty => 0,
+        Cell::Visited => 1,
+        Cell::Start => 2,
+        Cell::Head => 3,
+        Cell::Middle(_) => 4,
+        Cell::Tail => 5,
+    }
+}
+
+// Each frame is the knot positions after one step, with the tail's trail so far
+struct FrameRenderer<'a> {
+    bounds: &'a Bounds,
+    scale: usize,
+    trail: HashSet<Pos>,
+}
+
+impl FrameRenderer<'_> {
+    fn width(&self) -> usize {
+        self.bounds.width() * self.scale
+    }
+
+    fn height(&self) -> usize {
+        self.bounds.height() * self.scale
+    }
+
+    fn indexed_pixels(&self, knots: &[Pos]) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.width() * self.height());
+        for row in self.bounds.cells() {
+            let indices: Vec<u8> = row
+                .into_iter()
+                .map(|pos| palette_index(cell_at(pos, knots, &self.trail)))
+                .collect();
+            for _ in 0..self.scale {
+                for &index in indices.iter() {
+                    pixels.extend(std::iter::repeat_n(index, self.scale));
+                }
+            }
+        }
+        pixels
+    }
+}
+
+// Every frame extends the trail, but only every `every`th one (and the last) is drawn
+fn is_drawn(idx: usize, n_frames: usize, every: usize) -> bool {
+    idx.is_multiple_of(every) || idx + 1 == n_frames
+}
+
+pub fn write_ppm_frames(
+    frames: &[Vec<Pos>],
+    bounds: &Bounds,
+    dir: &Path,
+    scale: usize,
+    every: usize,
+) {
+    fs::create_dir_all(dir).expect("Couldn't create output directory");
+    let mut renderer = FrameRenderer {
+        bounds,
+        scale,
+        trail: HashSet::new(),
+    };
+    for (idx, knots) in frames.iter().enumerate() {
+        renderer.trail.insert(*knots.last().unwrap());
+        if !is_drawn(idx, frames.len(), every) {
+            continue;
+        }
+        let mut bytes =
+            format!("P6\n{} {}\n255\n", renderer.width(), renderer.height()).into_bytes();
+        for index in renderer.indexed_pixels(knots) {
+            bytes.extend_from_slice(&PALETTE[index as usize]);
+        }
+        fs::write(dir.join(format!("frame_{:06}.ppm", idx)), bytes).expect("Couldn't write frame");
+    }
+}
+
+// `delay` is in hundredths of a second per frame
+pub fn write_gif(
+    frames: &[Vec<Pos>],
+    bounds: &Bounds,
+    path: &Path,
+    scale: usize,
+    every: usize,
+    delay: u16,
+) {
+    let mut renderer = FrameRenderer {
+        bounds,
+        scale,
+        trail: HashSet::new(),
+    };
+    let width = u16::try_from(renderer.width()).expect("Grid is too big for a GIF");
+    let height = u16::try_from(renderer.height()).expect("Grid is too big for a GIF");
+    let palette: Vec<u8> = PALETTE.iter().flatten().copied().collect();
+    let file = File::create(path).expect("Couldn't create GIF");
+    let mut encoder = Encoder::new(file, width, height, &palette).expect("Couldn't start GIF");
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .expect("Couldn't write GIF");
+    for (idx, knots) in frames.iter().enumerate() {
+        renderer.trail.insert(*knots.last().unwrap());
+        if !is_drawn(idx, frames.len(), every) {
+            continue;
+        }
+        let mut frame =
+            Frame::from_indexed_pixels(width, height, &renderer.indexed_pixels(knots), None);
+        frame.delay = delay;
+        encoder
+            .write_frame(&frame)
+            .expect("Couldn't write GIF frame");
+    }
+}
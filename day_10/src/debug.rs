@@ -0,0 +1,214 @@
+use super::{print_grid, update_tube, Cpu, Instruction, RegisterState, Screen, TubeState};
+use std::fmt;
+use std::io::{self, Write};
+
+// What happened during one cycle
+pub struct TraceLine<'a> {
+    cycle: RegisterState,
+    instruction: &'a Instruction,
+    x_after: i32,
+    // Where the beam was and whether it lit the pixel
+    pixel: Option<((usize, usize), bool)>,
+}
+
+impl fmt::Display for TraceLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}  {:<12} x {:>4} -> {:<4}",
+            self.cycle.cycle_number,
+            self.instruction.to_string(),
+            self.cycle.x(),
+            self.x_after
+        )?;
+        match self.pixel {
+            Some(((row, col), lit)) => {
+                write!(f, " pixel {},{} {}", row, col, if lit { '#' } else { '.' })
+            }
+            None => write!(f, " pixel -"),
+        }
+    }
+}
+
+// The machine and its screen, advanced one cycle at a time
+pub struct Session<'a> {
+    cpu: Cpu<'a>,
+    tube: Option<TubeState>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(program: &'a [Instruction], screen: Screen) -> Session<'a> {
+        Session {
+            cpu: Cpu::new(program),
+            tube: Some(TubeState::new(screen)),
+        }
+    }
+
+    pub fn step(&mut self) -> Option<TraceLine<'a>> {
+        let (_, instruction) = self.cpu.upcoming()?;
+        let cycle = self.cpu.next()?;
+        let tube = update_tube(self.tube.take().unwrap(), cycle);
+        let pixel = tube
+            .last_drawn
+            .map(|(row, col)| ((row, col), tube.grid[row][col]));
+        self.tube = Some(tube);
+        Some(TraceLine {
+            cycle,
+            instruction,
+            x_after: self.cpu.state.x(),
+            pixel,
+        })
+    }
+
+    fn grid(&self) -> &[Vec<bool>] {
+        &self.tube.as_ref().unwrap().grid
+    }
+
+    fn describe(&self) -> String {
+        let registers = self.cpu.state.registers;
+        let shown: Vec<String> = ('a'..='z')
+            .filter(|&name| name == 'x' || registers.get(name) != 0)
+            .map(|name| format!("{}={}", name, registers.get(name)))
+            .collect();
+        match self.cpu.upcoming() {
+            Some((next, instruction)) => format!(
+                "cycle {} at {}: {}  {}",
+                next.cycle_number,
+                next.pc,
+                instruction,
+                shown.join(" ")
+            ),
+            None => format!(
+                "halted after cycle {}  {}",
+                self.cpu.state.cycle_number - 1,
+                shown.join(" ")
+            ),
+        }
+    }
+}
+
+pub fn trace(program: &[Instruction], screen: Screen) {
+    let mut session = Session::new(program, screen);
+    while let Some(line) = session.step() {
+        println!("{}", line);
+    }
+}
+
+// Checked against the state the next cycle will run with
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Breakpoint {
+    AtCycle(usize),
+    When(char, i32),
+}
+
+impl Breakpoint {
+    // `at N`, `at cycle N` or `when x == v`
+    fn parse(words: &[&str]) -> Result<Breakpoint, String> {
+        let usage = || "Breakpoints are `at cycle N` or `when x == v`".to_string();
+        match words {
+            ["at", cycle] | ["at", "cycle", cycle] => {
+                cycle.parse().map(Breakpoint::AtCycle).map_err(|_| usage())
+            }
+            ["when", register, "==", value] => {
+                let mut chars = register.chars();
+                match (chars.next(), chars.next(), value.parse()) {
+                    (Some(name), None, Ok(value)) if name.is_ascii_lowercase() => {
+                        Ok(Breakpoint::When(name, value))
+                    }
+                    _ => Err(usage()),
+                }
+            }
+            _ => Err(usage()),
+        }
+    }
+
+    fn hit(&self, next: &RegisterState) -> bool {
+        match *self {
+            Breakpoint::AtCycle(cycle) => next.cycle_number == cycle,
+            Breakpoint::When(name, value) => next.registers.get(name) == value,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::AtCycle(cycle) => write!(f, "at cycle {}", cycle),
+            Breakpoint::When(name, value) => write!(f, "when {} == {}", name, value),
+        }
+    }
+}
+
+// Runs at least one cycle, then until a breakpoint is hit or the program halts
+fn run_to_break(session: &mut Session, breakpoints: &[Breakpoint]) -> String {
+    let mut last = match session.step() {
+        Some(line) => line.to_string(),
+        None => return "The program has halted".to_string(),
+    };
+    loop {
+        let next = match session.cpu.upcoming() {
+            Some((next, _)) => next,
+            None => return format!("{}\nThe program has halted", last),
+        };
+        if let Some(breakpoint) = breakpoints.iter().find(|breakpoint| breakpoint.hit(&next)) {
+            return format!("{}\nStopped {}", last, breakpoint);
+        }
+        last = session.step().unwrap().to_string();
+    }
+}
+
+pub fn debugger(program: &[Instruction], screen: Screen) {
+    let mut session = Session::new(program, screen);
+    let mut breakpoints: Vec<Breakpoint> = vec![];
+    println!("{}", session.describe());
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Couldn't flush stdout");
+        let mut command = String::new();
+        if io::stdin()
+            .read_line(&mut command)
+            .expect("Couldn't read command")
+            == 0
+        {
+            return;
+        }
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["s"] | ["step"] => match session.step() {
+                Some(line) => println!("{}", line),
+                None => println!("The program has halted"),
+            },
+            ["s", n] | ["step", n] => match n.parse::<usize>() {
+                Ok(n) => {
+                    for line in (0..n).map_while(|_| session.step()) {
+                        println!("{}", line);
+                    }
+                }
+                Err(_) => println!("Bad step count {}", n),
+            },
+            ["c"] | ["continue"] => println!("{}", run_to_break(&mut session, &breakpoints)),
+            ["b", rest @ ..] | ["break", rest @ ..] if !rest.is_empty() => {
+                match Breakpoint::parse(rest) {
+                    Ok(breakpoint) => {
+                        breakpoints.push(breakpoint);
+                        println!("Breakpoint {}: {}", breakpoints.len(), breakpoint);
+                    }
+                    Err(err) => println!("{}", err),
+                }
+            }
+            ["b"] | ["break"] => {
+                for (idx, breakpoint) in breakpoints.iter().enumerate() {
+                    println!("Breakpoint {}: {}", idx + 1, breakpoint);
+                }
+            }
+            ["clear"] => breakpoints.clear(),
+            ["p"] | ["print"] => println!("{}", session.describe()),
+            ["p", "screen"] | ["print", "screen"] => print_grid(session.grid()),
+            ["q"] | ["quit"] => return,
+            _ => println!(
+                "Commands are step [N], continue, break at cycle N, break when x == v, \
+                 break, clear, print [screen] and quit"
+            ),
+        }
+    }
+}
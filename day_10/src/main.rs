@@ -1,139 +1,543 @@
-//use itertools::Itertools;
+mod debug;
+mod ocr;
+
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 
-#[derive(Debug)]
+// Registers are named a to z; x starts at 1 and the rest at 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Registers([i32; 26]);
+
+impl Default for Registers {
+    fn default() -> Self {
+        let mut registers = Registers([0; 26]);
+        registers.set('x', 1);
+        registers
+    }
+}
+
+impl Registers {
+    fn index(name: char) -> usize {
+        assert!(name.is_ascii_lowercase(), "Bad register {}", name);
+        (name as u8 - b'a') as usize
+    }
+
+    fn get(&self, name: char) -> i32 {
+        self.0[Registers::index(name)]
+    }
+
+    fn set(&mut self, name: char, value: i32) {
+        self.0[Registers::index(name)] = value;
+    }
+}
+
+// The machine during a single cycle, before the running instruction completes
+#[derive(Debug, Clone, Copy)]
 struct RegisterState {
     cycle_number: usize,
-    x: i32,
+    registers: Registers,
+    // Index of the instruction being executed
+    pc: usize,
+}
+
+impl RegisterState {
+    fn x(&self) -> i32 {
+        self.registers.get('x')
+    }
+}
+
+// What the CRT does once the beam passes the bottom of the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overflow {
+    // Ignore any further cycles
+    Stop,
+    // Start drawing over the top row again
+    Wrap,
+    // Drop the top row and keep drawing on a fresh bottom row
+    Scroll,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Screen {
+    width: usize,
+    height: usize,
+    sprite_width: usize,
+    overflow: Overflow,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+            overflow: Overflow::Stop,
+        }
+    }
+}
+
+impl Screen {
+    // The sprite is centred on x, leaning right when its width is even
+    fn sprite_covers(&self, x: i32, col: usize) -> bool {
+        let left = x - (self.sprite_width as i32 - 1) / 2;
+        (left..left + self.sprite_width as i32).contains(&(col as i32))
+    }
 }
 
 #[derive(Debug)]
 struct TubeState {
-    register: RegisterState,
+    screen: Screen,
     crt_position: (usize, usize),
     grid: Vec<Vec<bool>>,
+    // Pixel drawn by the latest cycle, if the screen wasn't full
+    last_drawn: Option<(usize, usize)>,
 }
 
-#[derive(Debug)]
-enum CycleOp {
-    Hang,
-    AddX(i32),
+impl TubeState {
+    fn new(screen: Screen) -> TubeState {
+        TubeState {
+            screen,
+            crt_position: (0, 0),
+            grid: vec![vec![false; screen.width]; screen.height],
+            last_drawn: None,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.crt_position.0 >= self.screen.height
+    }
+}
+
+// Cycles that the signal strength is sampled during
+#[derive(Debug, Clone, PartialEq)]
+enum Probes {
+    Cycles(Vec<usize>),
+    // Every step cycles from start, for count probes or until the program halts
+    Schedule {
+        start: usize,
+        step: usize,
+        count: Option<usize>,
+    },
 }
 
-impl Default for RegisterState {
+impl Default for Probes {
     fn default() -> Self {
-        Self {
-            cycle_number: 1,
-            x: 1,
+        Probes::Schedule {
+            start: 20,
+            step: 40,
+            count: Some(6),
         }
     }
 }
 
-impl Default for TubeState {
-    fn default() -> Self {
-        Self {
-            register: RegisterState::default(),
-            crt_position: (0, 0),
-            grid: vec![vec![false; 40]; 6],
+impl Probes {
+    // Either a list like 20,60,100 or a schedule like 20+40 or 20+40x6
+    fn parse(spec: &str) -> Probes {
+        let number = |text: &str| -> usize {
+            text.parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .unwrap_or_else(|| panic!("Bad probe specification {}", spec))
+        };
+        match spec.split_once('+') {
+            Some((start, rest)) => {
+                let (step, count) = match rest.split_once('x') {
+                    Some((step, count)) => (step, Some(number(count))),
+                    None => (rest, None),
+                };
+                Probes::Schedule {
+                    start: number(start),
+                    step: number(step),
+                    count,
+                }
+            }
+            None => Probes::Cycles(spec.split(',').map(number).collect()),
+        }
+    }
+
+    fn contains(&self, cycle: usize) -> bool {
+        match self {
+            Probes::Cycles(cycles) => cycles.contains(&cycle),
+            Probes::Schedule { start, step, count } => {
+                cycle >= *start
+                    && (cycle - start).is_multiple_of(*step)
+                    && count.is_none_or(|count| (cycle - start) / step < count)
+            }
+        }
+    }
+
+    // The last cycle worth running to, if there is one
+    fn last(&self) -> Option<usize> {
+        match self {
+            Probes::Cycles(cycles) => cycles.iter().copied().max(),
+            Probes::Schedule { start, step, count } => {
+                count.map(|count| start + step * (count - 1))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OperandKind {
+    Register,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Register(char),
+    Value(i32),
+}
+
+impl Operand {
+    fn register(&self) -> char {
+        match self {
+            Operand::Register(name) => *name,
+            Operand::Value(_) => panic!("Expected a register operand"),
+        }
+    }
+
+    fn value(&self) -> i32 {
+        match self {
+            Operand::Value(value) => *value,
+            Operand::Register(_) => panic!("Expected a value operand"),
         }
     }
 }
 
-fn parse_line(line: Vec<&str>) -> Vec<CycleOp> {
-    match line[0] {
-        "noop" => vec![CycleOp::Hang],
-        "addx" => {
-            let value: i32 = line[1].parse().expect("addx value was not an integer");
-            vec![CycleOp::Hang, CycleOp::AddX(value)]
+// What happens to the program counter once an instruction completes
+enum Flow {
+    Next,
+    Jump(i32),
+}
+
+struct InstructionDef {
+    name: &'static str,
+    operands: &'static [OperandKind],
+    cycles: usize,
+    // Fails with the name of a register whose new value doesn't fit
+    effect: fn(&mut Registers, &[Operand]) -> Result<Flow, char>,
+}
+
+const INSTRUCTIONS: &[InstructionDef] = &[
+    InstructionDef {
+        name: "noop",
+        operands: &[],
+        cycles: 1,
+        effect: |_, _| Ok(Flow::Next),
+    },
+    InstructionDef {
+        name: "addx",
+        operands: &[OperandKind::Value],
+        cycles: 2,
+        effect: |registers, operands| {
+            let x = registers.get('x').checked_add(operands[0].value());
+            registers.set('x', x.ok_or('x')?);
+            Ok(Flow::Next)
+        },
+    },
+    InstructionDef {
+        name: "mulx",
+        operands: &[OperandKind::Value],
+        cycles: 2,
+        effect: |registers, operands| {
+            let x = registers.get('x').checked_mul(operands[0].value());
+            registers.set('x', x.ok_or('x')?);
+            Ok(Flow::Next)
+        },
+    },
+    InstructionDef {
+        name: "set",
+        operands: &[OperandKind::Register, OperandKind::Value],
+        cycles: 1,
+        effect: |registers, operands| {
+            registers.set(operands[0].register(), operands[1].value());
+            Ok(Flow::Next)
+        },
+    },
+    InstructionDef {
+        name: "add",
+        operands: &[OperandKind::Register, OperandKind::Value],
+        cycles: 2,
+        effect: |registers, operands| {
+            let name = operands[0].register();
+            let value = registers.get(name).checked_add(operands[1].value());
+            registers.set(name, value.ok_or(name)?);
+            Ok(Flow::Next)
+        },
+    },
+    InstructionDef {
+        name: "jmp",
+        operands: &[OperandKind::Value],
+        cycles: 1,
+        effect: |_, operands| Ok(Flow::Jump(operands[0].value())),
+    },
+    // Jump by the offset if the register is not zero
+    InstructionDef {
+        name: "jnz",
+        operands: &[OperandKind::Register, OperandKind::Value],
+        cycles: 2,
+        effect: |registers, operands| {
+            if registers.get(operands[0].register()) != 0 {
+                Ok(Flow::Jump(operands[1].value()))
+            } else {
+                Ok(Flow::Next)
+            }
+        },
+    },
+];
+
+struct Instruction {
+    def: &'static InstructionDef,
+    operands: Vec<Operand>,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.def.name)?;
+        for operand in self.operands.iter() {
+            match operand {
+                Operand::Register(name) => write!(f, " {}", name)?,
+                Operand::Value(value) => write!(f, " {}", value)?,
+            }
         }
-        _ => panic!("Bad operation"),
+        Ok(())
     }
 }
 
-fn read_lines() -> impl Iterator<Item = CycleOp> {
+fn parse_operand(kind: OperandKind, operand: &str) -> Operand {
+    match kind {
+        OperandKind::Value => Operand::Value(operand.parse().expect("Operand was not an integer")),
+        OperandKind::Register => {
+            let mut chars = operand.chars();
+            match (chars.next(), chars.next()) {
+                (Some(name), None) if name.is_ascii_lowercase() => Operand::Register(name),
+                _ => panic!("Operand {} is not a register", operand),
+            }
+        }
+    }
+}
+
+fn parse_line(line: Vec<&str>) -> Instruction {
+    let def = INSTRUCTIONS
+        .iter()
+        .find(|def| def.name == line[0])
+        .unwrap_or_else(|| panic!("Bad operation {}", line[0]));
+    assert!(
+        line.len() - 1 == def.operands.len(),
+        "{} takes {} operands",
+        def.name,
+        def.operands.len()
+    );
+    let operands = def
+        .operands
+        .iter()
+        .zip(line[1..].iter())
+        .map(|(&kind, operand)| parse_operand(kind, operand))
+        .collect();
+    Instruction { def, operands }
+}
+
+fn read_lines() -> Vec<Instruction> {
     let file = File::open("./data/input").expect("Input file not found");
     let lines = io::BufReader::new(file).lines();
     lines
         .map(|line| line.expect("Couldn't read line of input"))
-        .map(|line| {
-            let split_line = line.split(" ").collect();
-            let move_vec = parse_line(split_line);
-            return move_vec.into_iter();
-        })
-        .flatten()
-}
-
-fn update_register(state: RegisterState, op: CycleOp) -> RegisterState {
-    let cycle_number = state.cycle_number + 1;
-    let x = match op {
-        CycleOp::Hang => state.x,
-        CycleOp::AddX(x_to_add) => state.x + x_to_add,
-    };
-    RegisterState { cycle_number, x }
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_line(line.split_whitespace().collect()))
+        .collect()
+}
+
+// Steps through a program one cycle at a time, yielding the state during each
+// cycle. Runs until the program counter leaves the program
+struct Cpu<'a> {
+    program: &'a [Instruction],
+    state: RegisterState,
+    // Cycles left on the running instruction, 0 if the next one needs fetching
+    remaining: usize,
+}
+
+impl<'a> Cpu<'a> {
+    fn new(program: &'a [Instruction]) -> Cpu<'a> {
+        Cpu {
+            program,
+            state: RegisterState {
+                cycle_number: 1,
+                registers: Registers::default(),
+                pc: 0,
+            },
+            remaining: 0,
+        }
+    }
+
+    // The state during the next cycle, or None once the program has halted
+    fn upcoming(&self) -> Option<(RegisterState, &'a Instruction)> {
+        let instruction = self.program.get(self.state.pc)?;
+        Some((self.state, instruction))
+    }
 }
 
-fn update_tube(state: TubeState, op: CycleOp) -> TubeState {
+impl Iterator for Cpu<'_> {
+    type Item = RegisterState;
+
+    fn next(&mut self) -> Option<RegisterState> {
+        let instruction = self.program.get(self.state.pc)?;
+        if self.remaining == 0 {
+            self.remaining = instruction.def.cycles;
+        }
+        let during = self.state;
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            let flow = (instruction.def.effect)(&mut self.state.registers, &instruction.operands)
+                .unwrap_or_else(|name| {
+                    panic!(
+                        "Register {} overflowed at cycle {} running {}",
+                        name, self.state.cycle_number, instruction
+                    )
+                });
+            self.state.pc = match flow {
+                Flow::Next => self.state.pc + 1,
+                // Jumping before the start halts, just like running off the end
+                Flow::Jump(offset) => self
+                    .state
+                    .pc
+                    .checked_add_signed(offset as isize)
+                    .unwrap_or(usize::MAX),
+            };
+        }
+        self.state.cycle_number += 1;
+        Some(during)
+    }
+}
+
+fn update_tube(state: TubeState, cycle: RegisterState) -> TubeState {
     // Destructure
+    let screen = state.screen;
     let mut current_grid = state.grid;
-    let current_pos = state.crt_position;
+    let mut current_pos = state.crt_position;
+    if current_pos.0 >= screen.height {
+        match screen.overflow {
+            Overflow::Stop => {
+                return TubeState {
+                    screen,
+                    crt_position: current_pos,
+                    grid: current_grid,
+                    last_drawn: None,
+                }
+            }
+            Overflow::Wrap => current_pos.0 = 0,
+            Overflow::Scroll => {
+                current_grid.remove(0);
+                current_grid.push(vec![false; screen.width]);
+                current_pos.0 = screen.height - 1;
+            }
+        }
+    }
     // Colour in grid
-    current_grid[current_pos.0][current_pos.1] =
-        (state.register.x - (current_pos.1 as i32)).abs() <= 1;
+    current_grid[current_pos.0][current_pos.1] = screen.sprite_covers(cycle.x(), current_pos.1);
     // Update position
     let mut new_pos = (current_pos.0, current_pos.1 + 1);
-    if new_pos.1 >= 40 {
+    if new_pos.1 >= screen.width {
         new_pos.1 = 0;
         new_pos.0 += 1;
     }
     // Return
     TubeState {
-        register: update_register(state.register, op),
+        screen,
         crt_position: new_pos,
         grid: current_grid,
+        last_drawn: Some(current_pos),
     }
 }
 
 fn get_strength(state: &RegisterState) -> i32 {
-    state.x * (state.cycle_number as i32)
+    state.x() * (state.cycle_number as i32)
 }
 
-fn print_grid(grid: Vec<Vec<bool>>) {
+fn print_grid(grid: &[Vec<bool>]) {
     for row in grid {
-        for cell in row {
+        for &cell in row {
             if cell {
                 print!("#")
             } else {
                 print!(".")
             }
         }
-        print!("\n")
+        println!()
     }
 }
 
-fn part_a() {
-    let mut op_iter = read_lines();
-    // We take 19 so that internal cycle number hits 20
-    let mut state = op_iter
-        .by_ref()
-        .take(19)
-        .fold(RegisterState::default(), update_register);
-    // Then take 40 five more times
-    let mut sum_of_strengths: i32 = get_strength(&state);
-    for _ in 0..5 {
-        state = op_iter.by_ref().take(40).fold(state, update_register);
-        sum_of_strengths += get_strength(&state);
-    }
+fn part_a(program: &[Instruction], probes: &Probes) {
+    let last = probes.last().unwrap_or(usize::MAX);
+    let sum_of_strengths: i32 = Cpu::new(program)
+        .take_while(|state| state.cycle_number <= last)
+        .filter(|state| probes.contains(state.cycle_number))
+        .map(|state| get_strength(&state))
+        .sum();
     println!("{}", sum_of_strengths);
 }
 
-fn part_b() {
-    let op_iter = read_lines();
-    let state = op_iter.fold(TubeState::default(), update_tube);
-    print_grid(state.grid);
+// Unless the screen stops when full, the whole program is drawn
+fn part_b(program: &[Instruction], screen: Screen) {
+    let mut cycles = Cpu::new(program);
+    let mut state = TubeState::new(screen);
+    while !(screen.overflow == Overflow::Stop && state.is_full()) {
+        match cycles.next() {
+            Some(cycle) => state = update_tube(state, cycle),
+            None => break,
+        }
+    }
+    print_grid(&state.grid);
+    match ocr::read_letters(&state.grid) {
+        Ok(letters) => println!("{}", letters),
+        Err(err) => eprintln!("Couldn't read the screen: {}", err),
+    }
 }
 
 fn main() {
-    part_a();
-    part_b();
+    let mut args = env::args().skip(1).peekable();
+    let mode = match args.peek().map(|arg| arg.as_str()) {
+        Some("trace") | Some("debug") => args.next(),
+        _ => None,
+    };
+    let mut probes = Probes::default();
+    let mut screen = Screen::default();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .unwrap_or_else(|| panic!("{} should be followed by a value", arg));
+        let size = || -> usize {
+            value
+                .parse()
+                .ok()
+                .filter(|&size| size > 0)
+                .unwrap_or_else(|| panic!("{} should be followed by a positive integer", arg))
+        };
+        match arg.as_str() {
+            "--probes" => probes = Probes::parse(&value),
+            "--width" => screen.width = size(),
+            "--height" => screen.height = size(),
+            "--sprite" => screen.sprite_width = size(),
+            "--overflow" => {
+                screen.overflow = match value.as_str() {
+                    "stop" => Overflow::Stop,
+                    "wrap" => Overflow::Wrap,
+                    "scroll" => Overflow::Scroll,
+                    _ => panic!("Overflow should be stop, wrap or scroll"),
+                }
+            }
+            _ => panic!("Unknown option {}", arg),
+        }
+    }
+
+    let program = read_lines();
+    match mode.as_deref() {
+        Some("trace") => return debug::trace(&program, screen),
+        Some("debug") => return debug::debugger(&program, screen),
+        _ => {}
+    }
+    part_a(&program, &probes);
+    part_b(&program, screen);
 }
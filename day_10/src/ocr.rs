@@ -0,0 +1,107 @@
+use std::fmt;
+
+// The Advent of Code CRT font: 4x6 letters, each followed by a blank column
+const GLYPH_HEIGHT: usize = 6;
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_SPACING: usize = 5;
+
+const FONT: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+#[derive(Debug)]
+pub struct UnknownGlyph {
+    // Position of the glyph along the screen, from 0
+    pub index: usize,
+    pub bitmap: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum OcrError {
+    WrongHeight(usize),
+    UnknownGlyphs(Vec<UnknownGlyph>),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::WrongHeight(height) => write!(
+                f,
+                "screen is {} rows high but letters are {}",
+                height, GLYPH_HEIGHT
+            ),
+            OcrError::UnknownGlyphs(glyphs) => {
+                for (idx, glyph) in glyphs.iter().enumerate() {
+                    if idx > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "unknown glyph at position {}:", glyph.index)?;
+                    for row in glyph.bitmap.iter() {
+                        write!(f, "\n{}", row)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn glyph_bitmap(grid: &[Vec<bool>], index: usize) -> Vec<String> {
+    let start = index * GLYPH_SPACING;
+    grid.iter()
+        .map(|row| {
+            (start..start + GLYPH_WIDTH)
+                .map(|col| match row.get(col) {
+                    Some(true) => '#',
+                    _ => '.',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn recognise(bitmap: &[String]) -> Option<char> {
+    FONT.iter()
+        .find(|(_, rows)| rows.iter().zip(bitmap.iter()).all(|(a, b)| a == b))
+        .map(|&(letter, _)| letter)
+}
+
+// Reads the screen as a line of letters, one every five columns
+pub fn read_letters(grid: &[Vec<bool>]) -> Result<String, OcrError> {
+    if grid.len() != GLYPH_HEIGHT {
+        return Err(OcrError::WrongHeight(grid.len()));
+    }
+    let width = grid[0].len();
+    let n_glyphs = width.div_ceil(GLYPH_SPACING);
+    let mut letters = String::new();
+    let mut unknown = vec![];
+    for index in 0..n_glyphs {
+        let bitmap = glyph_bitmap(grid, index);
+        match recognise(&bitmap) {
+            Some(letter) => letters.push(letter),
+            None => unknown.push(UnknownGlyph { index, bitmap }),
+        }
+    }
+    if unknown.is_empty() {
+        Ok(letters)
+    } else {
+        Err(OcrError::UnknownGlyphs(unknown))
+    }
+}
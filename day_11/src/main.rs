@@ -1,10 +1,14 @@
 use num::integer::lcm;
+use num_bigint::BigUint;
 use std::cmp::Reverse;
 use std::fs::File;
 use std::io::{self, BufRead};
 extern crate queues;
 use queues::*;
 
+// Worry levels are kept exact with relief, and soon outgrow any fixed width
+type Worry = BigUint;
+
 struct MonkeyTest {
     divisor: u64,
     target_if_true: usize,
@@ -18,7 +22,7 @@ enum MonkeyOp {
 }
 
 struct Monkey {
-    items: Queue<u64>,
+    items: Queue<Worry>,
     op: MonkeyOp,
     test: MonkeyTest,
     inspected: u64,
@@ -35,7 +39,7 @@ fn parse_monkey(manifest: &[String]) -> Monkey {
     let item_list = manifest[1].trim().replace(",", "");
     let split_item_list: Vec<&str> = item_list.split(" ").collect();
     // Throw away first two words
-    let mut items: Queue<u64> = queue![];
+    let mut items: Queue<Worry> = queue![];
     for (i, item) in split_item_list.iter().enumerate() {
         if i <= 1 {
             continue;
@@ -78,7 +82,11 @@ fn parse_mob() -> MonkeyMob {
     let lines_vec: Vec<String> = lines
         .map(|line| line.expect("Couldn't read line of input"))
         .collect();
-    let mob = lines_vec
+    mob_from_lines(&lines_vec)
+}
+
+fn mob_from_lines(lines: &[String]) -> MonkeyMob {
+    let mob = lines
         .split(|line| line.is_empty())
         .map(parse_monkey)
         .collect();
@@ -89,26 +97,30 @@ fn parse_mob() -> MonkeyMob {
     }
 }
 
-fn apply_operation(item: u64, op: &MonkeyOp) -> u64 {
+fn apply_operation(item: Worry, op: &MonkeyOp) -> Worry {
     match op {
-        MonkeyOp::Multiply(n) => item * n,
-        MonkeyOp::Add(n) => item + n,
-        MonkeyOp::Square => item * item,
+        MonkeyOp::Multiply(n) => item * *n,
+        MonkeyOp::Add(n) => item + *n,
+        MonkeyOp::Square => &item * &item,
     }
 }
 
-fn get_bored(item: u64) -> u64 {
-    (item as f32 / 3.0).floor() as u64
+fn get_bored(item: Worry) -> Worry {
+    item / 3u32
 }
 
-fn determine_target(item: u64, test: &MonkeyTest) -> usize {
-    if item % test.divisor == 0 {
+fn determine_target(item: &Worry, test: &MonkeyTest) -> usize {
+    if item % test.divisor == BigUint::ZERO {
         test.target_if_true
     } else {
         test.target_if_false
     }
 }
 
+// Worry levels only matter through the divisibility tests, so without relief
+// they can be kept modulo the lcm of the divisors. With relief that isn't
+// enough: a level modulo 3 * lcm gives the next one modulo lcm, but the
+// division after that would need it modulo 3 * lcm again, so they're kept exact
 fn play(mob: &mut MonkeyMob, should_bore: bool, mob_lcm: u64) {
     let working_monkey = &mut mob.mob[mob.current];
     if let Ok(item) = working_monkey.items.remove() {
@@ -116,10 +128,10 @@ fn play(mob: &mut MonkeyMob, should_bore: bool, mob_lcm: u64) {
         working_monkey.inspected += 1;
         if should_bore {
             new_worry = get_bored(new_worry);
+        } else {
+            new_worry %= mob_lcm;
         }
-        // Modulo by all divisiblity test to prevent blow up
-        new_worry = new_worry % mob_lcm;
-        let target = determine_target(new_worry, &working_monkey.test);
+        let target = determine_target(&new_worry, &working_monkey.test);
         let target_monkey = &mut mob.mob[target];
         target_monkey
             .items
@@ -173,3 +185,174 @@ fn main() {
     part_a();
     part_b();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    const SAMPLE: &str = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    fn sample_mob() -> MonkeyMob {
+        let lines: Vec<String> = SAMPLE.lines().map(|line| line.to_string()).collect();
+        mob_from_lines(&lines)
+    }
+
+    // The same rules with no reduction at all
+    fn reference_inspections(mob: &mut MonkeyMob, rounds: u64, should_bore: bool) -> Vec<u64> {
+        // Queues can't be cloned, so cycle through each one to copy it
+        let mut items: Vec<VecDeque<BigUint>> = mob
+            .mob
+            .iter_mut()
+            .map(|monkey| {
+                (0..monkey.items.size())
+                    .map(|_| {
+                        let item = monkey.items.remove().unwrap();
+                        monkey.items.add(item.clone()).unwrap();
+                        item
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut inspected = vec![0; mob.mob.len()];
+        for _ in 0..rounds {
+            for (idx, monkey) in mob.mob.iter().enumerate() {
+                while let Some(item) = items[idx].pop_front() {
+                    inspected[idx] += 1;
+                    let mut worry = match monkey.op {
+                        MonkeyOp::Multiply(n) => item * n,
+                        MonkeyOp::Add(n) => item + n,
+                        MonkeyOp::Square => &item * &item,
+                    };
+                    if should_bore {
+                        worry /= 3u32;
+                    }
+                    let target = if (&worry % monkey.test.divisor) == BigUint::ZERO {
+                        monkey.test.target_if_true
+                    } else {
+                        monkey.test.target_if_false
+                    };
+                    items[target].push_back(worry);
+                }
+            }
+        }
+        inspected
+    }
+
+    fn inspections(mut mob: MonkeyMob, rounds: u64, should_bore: bool) -> Vec<u64> {
+        let mob_lcm = get_lcm(&mob);
+        while mob.round <= rounds {
+            play(&mut mob, should_bore, mob_lcm);
+        }
+        mob.mob.iter().map(|monkey| monkey.inspected).collect()
+    }
+
+    fn assert_parity(mut mob: MonkeyMob, rounds: u64, should_bore: bool) {
+        let expected = reference_inspections(&mut mob, rounds, should_bore);
+        assert_eq!(inspections(mob, rounds, should_bore), expected);
+    }
+
+    // xorshift, so the random mobs are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+
+        fn other_monkey(&mut self, idx: usize, n_monkeys: usize) -> usize {
+            (idx + 1 + self.below(n_monkeys as u64 - 1) as usize) % n_monkeys
+        }
+    }
+
+    // Only the first monkey may square, so an item is squared at most once a round
+    fn random_mob(rng: &mut Rng) -> MonkeyMob {
+        let n_monkeys = 2 + rng.below(4) as usize;
+        let mob = (0..n_monkeys)
+            .map(|idx| {
+                let mut items: Queue<Worry> = queue![];
+                for _ in 0..rng.below(5) {
+                    items.add(Worry::from(1 + rng.below(100))).unwrap();
+                }
+                let op = match rng.below(3) {
+                    0 if idx == 0 => MonkeyOp::Square,
+                    0 | 1 => MonkeyOp::Multiply(1 + rng.below(20)),
+                    _ => MonkeyOp::Add(1 + rng.below(20)),
+                };
+                let test = MonkeyTest {
+                    divisor: [2, 3, 5, 7, 11, 13, 17, 19][rng.below(8) as usize],
+                    target_if_true: rng.other_monkey(idx, n_monkeys),
+                    target_if_false: rng.other_monkey(idx, n_monkeys),
+                };
+                Monkey {
+                    items,
+                    op,
+                    test,
+                    inspected: 0,
+                }
+            })
+            .collect();
+        MonkeyMob {
+            mob,
+            current: 0,
+            round: 1,
+        }
+    }
+
+    #[test]
+    fn sample_with_relief() {
+        assert_parity(sample_mob(), 20, true);
+        assert_eq!(inspections(sample_mob(), 20, true), vec![101, 95, 7, 105]);
+    }
+
+    #[test]
+    fn sample_without_relief() {
+        assert_parity(sample_mob(), 20, false);
+        assert_eq!(inspections(sample_mob(), 20, false), vec![99, 97, 8, 103]);
+    }
+
+    #[test]
+    fn random_mobs_with_relief() {
+        let mut rng = Rng(0x5eed);
+        for _ in 0..50 {
+            assert_parity(random_mob(&mut rng), 10, true);
+        }
+    }
+
+    #[test]
+    fn random_mobs_without_relief() {
+        let mut rng = Rng(0xfeed);
+        for _ in 0..50 {
+            assert_parity(random_mob(&mut rng), 10, false);
+        }
+    }
+}